@@ -1,8 +1,8 @@
 #![warn(clippy::all, rust_2018_idioms)]
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 
+use earthrs_modis::render::{render, Colormap, RenderOptions, Stretch};
 use earthrs_modis::*;
-use egui::{Painter, Pos2, Rect, Sense, Vec2};
 
 // Latitude and Longitude constants
 // Lat is -90 to 90
@@ -75,7 +75,7 @@ fn main() -> eframe::Result {
     )
 }
 
-pub struct LaiApp {
+struct LaiApp {
     // rx
     rx: mpsc::Receiver<Message>,
     tx_cmd: mpsc::Sender<Command>,
@@ -84,6 +84,7 @@ pub struct LaiApp {
     selected_date: Option<DateInfo>,
 
     modis_data: Option<ModisData>,
+    texture: Option<egui::TextureHandle>,
 }
 
 impl Default for LaiApp {
@@ -95,14 +96,15 @@ impl Default for LaiApp {
             dates: None,
             selected_date: None,
             modis_data: None,
+            texture: None,
         }
     }
 }
 
 impl LaiApp {
     /// Called once before the first frame.
-    pub fn new(
-        cc: &eframe::CreationContext<'_>,
+    fn new(
+        _cc: &eframe::CreationContext<'_>,
         rx: mpsc::Receiver<Message>,
         tx_cmd: mpsc::Sender<Command>,
     ) -> Self {
@@ -127,8 +129,27 @@ impl eframe::App for LaiApp {
                     // Set the last date as the selected date
                     self.selected_date = Some(dates.dates.last().unwrap().clone());
                     self.dates = Some(dates);
-                },
+                }
                 Message::Data(data) => {
+                    // LAI is 0-10 m^2/m^2, fill values (> 100 raw) are masked by the band mask
+                    let options = RenderOptions {
+                        colormap: Colormap::Ndvi,
+                        stretch: Stretch::Fixed(0.0, 7.0),
+                        upscale: 25,
+                        legend: true,
+                        ..Default::default()
+                    };
+                    self.texture = data.subset.first().map(|subset| {
+                        let image = render(&data, subset, &options);
+                        ctx.load_texture(
+                            "lai",
+                            egui::ColorImage::from_rgba_unmultiplied(
+                                [image.width as usize, image.height as usize],
+                                &image.pixels,
+                            ),
+                            egui::TextureOptions::NEAREST,
+                        )
+                    });
                     self.modis_data = Some(data);
                 }
             }
@@ -192,68 +213,9 @@ impl eframe::App for LaiApp {
             }
 
             // If we have data, display it
-            // ModisData { xllcorner: "13213215.26", yllcorner: "-5101536.36", cellsize: 463.312716528, nrows: 5, ncols: 5, band: "Lai_500m", units: "m^2/m^2", scale: "0.1", latitude: -45.8667, longitude: 170.6667, header: "https://modisrest.ornl.gov/rst/api/v1/MCD15A2H/subset?latitude=-45.8667&longitude=170.6667&band=Lai_500m&startDate=A2024217&endDate=A2024217&kmAboveBelow=1&kmLeftRight=1", subset: [Subset { modis_date: "A2024217", calendar_date: "2024-08-04", band: "Lai_500m", tile: "h29v13", proc_date: "2024228030354", data: [13, 13, 9, 9, 6, 10, 9, 4, 254, 254, 11, 1, 254, 3, 9, 5, 5, 5, 9, 11, 1, 1, 18, 254, 254] }] }
-            // Given nrows, ncols. Plot rectangles of size cellsize and color based on data
-
-            if let Some(data) = &self.modis_data {
-                // Draw away from the widgets
-                ui.horizontal(|ui| {
-                    let next_widget_pos = ui.next_widget_position();
-
-                    // Draw the data
-                    for dat in &data.subset {
-                        // Get nrows and ncols
-                        let nrows = data.nrows;
-                        let ncols = data.ncols;
-
-                        // Get cellsize
-                        let cellsize = data.cellsize as f32;
-                        let cellsize = 25.0;
-
-                        // Get data
-                        let data = &dat.data;
-
-                        // Draw the data
-                        let mut i = 0;
-                        for dat in data {
-
-                            // Determine the x and y offset based off of i
-                            let x = i % ncols;
-                            let y = i / ncols;
-
-                            // Draw a square
-                            let rect = egui::Rect::from_min_size(
-                                [x as f32 * cellsize + next_widget_pos.x, y as f32 * cellsize + next_widget_pos.y].into(),
-                                [cellsize, cellsize].into(),
-                            );
-
-                            // RGB should be a scale from 0 to 255
-                            // Transparent if 0 to dark green at 255
-                            let color = if *dat == 254 {
-                                egui::Color32::TRANSPARENT
-                            } else {
-                                let scale = (*dat as f32) / 255.0;
-                                egui::Color32::from_rgb(
-                                    (255.0 * scale) as u8,
-                                    (255.0 * (1.0 - scale)) as u8,
-                                    0,
-                                )
-                            };
-                            ui.painter().rect_filled(
-                                rect,
-                                0.0,
-                                color,
-                            );
-
-                            i += 1;
-                        }
-                    }
-
-                });
+            if let Some(texture) = &self.texture {
+                ui.image(texture);
             }
-            // Draw a square at 0,0
-            // let rect = egui::Rect::from_min_size([0.0, 0.0].into(), [100.0, 100.0].into());
-            // ui.painter().rect_filled(rect, 0.0, egui::Color32::from_rgb(100, 200, 100));
         });
     }
 }
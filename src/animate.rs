@@ -0,0 +1,168 @@
+// Animated GIF/APNG time-lapse of all dates in a ModisData
+// Every frame shares one stretch, resolved over all dates, so colours are comparable
+
+use std::io::Write;
+
+use crate::render::{render_with_range, valid_values, RenderOptions, RgbaImage, BLACK, WHITE};
+use crate::structs::ModisData;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnimationFormat {
+    Gif,
+    Apng,
+}
+
+/// Render one frame per date, each with its calendar date in a strip below the map
+///
+/// Frames are widened to fit the date when the map is narrower, with the map centred.
+pub fn render_frames(data: &ModisData, options: &RenderOptions) -> Vec<RgbaImage> {
+    let mask = options.mask.clone().unwrap_or_else(|| data.mask());
+    let values: Vec<f64> = data
+        .subset
+        .iter()
+        .flat_map(|subset| valid_values(data, subset, &mask))
+        .collect();
+    let range = options.stretch.range(&values);
+
+    data.subset
+        .iter()
+        .map(|subset| {
+            let map = render_with_range(data, subset, options, range);
+            let fs = (map.width / 60).clamp(1, 4);
+            let pad = 2 * fs;
+            let label = RgbaImage::text_width(&subset.calendar_date, fs) + 2 * pad;
+            let width = map.width.max(label);
+            let mut frame = RgbaImage::new(width, map.height + 5 * fs + pad * 2, WHITE);
+            frame.blit(&map, (width - map.width) / 2, 0);
+            frame.draw_text(pad, map.height + pad, &subset.calendar_date, fs, BLACK);
+            frame
+        })
+        .collect()
+}
+
+// Every frame the same size and no side over `max`
+fn check_size(frames: &[RgbaImage], max: u32) -> Result<(), Box<dyn std::error::Error>> {
+    let first = &frames[0];
+    for frame in frames {
+        if (frame.width, frame.height) != (first.width, first.height) {
+            return Err("animation frames differ in size".into());
+        }
+    }
+    if first.width > max || first.height > max {
+        return Err(format!(
+            "{}x{} frames are too large for an animation, at most {} per side",
+            first.width, first.height, max
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Write frames as a looping GIF, `delay_ms` per frame
+pub fn write_gif<W: Write>(
+    frames: &[RgbaImage],
+    delay_ms: u32,
+    writer: W,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let first = frames.first().ok_or("no frames to write")?;
+    check_size(frames, u16::MAX as u32)?;
+    let mut encoder = gif::Encoder::new(writer, first.width as u16, first.height as u16, &[])?;
+    encoder.set_repeat(gif::Repeat::Infinite)?;
+    for image in frames {
+        let mut pixels = image.pixels.clone();
+        let mut frame =
+            gif::Frame::from_rgba_speed(image.width as u16, image.height as u16, &mut pixels, 10);
+        frame.delay = (delay_ms / 10).min(u16::MAX as u32) as u16;
+        encoder.write_frame(&frame)?;
+    }
+    Ok(())
+}
+
+/// Write frames as a looping APNG, `delay_ms` per frame
+pub fn write_apng<W: Write>(
+    frames: &[RgbaImage],
+    delay_ms: u32,
+    writer: W,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let first = frames.first().ok_or("no frames to write")?;
+    check_size(frames, u16::MAX as u32)?;
+    let mut encoder = png::Encoder::new(writer, first.width, first.height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_animated(frames.len() as u32, 0)?;
+    encoder.set_frame_delay(delay_ms.min(u16::MAX as u32) as u16, 1000)?;
+    let mut writer = encoder.write_header()?;
+    for frame in frames {
+        writer.write_image_data(&frame.pixels)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+impl ModisData {
+    /// Render every date and write an animated GIF or APNG
+    pub fn write_animation<W: Write>(
+        &self,
+        writer: W,
+        format: AnimationFormat,
+        options: &RenderOptions,
+        delay_ms: u32,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let frames = render_frames(self, options);
+        match format {
+            AnimationFormat::Gif => write_gif(&frames, delay_ms, writer),
+            AnimationFormat::Apng => write_apng(&frames, delay_ms, writer),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::Stretch;
+    use crate::tests::otago;
+
+    #[test]
+    fn test_animation() {
+        let mut data = otago();
+        let mut later = data.subset[0].clone();
+        later.modis_date = "A2024225".to_string();
+        later.calendar_date = "2024-08-12".to_string();
+        later.data = later
+            .data
+            .iter()
+            .map(|v| if *v < 100 { v + 5 } else { *v })
+            .collect();
+        data.subset.push(later);
+
+        let options = RenderOptions {
+            stretch: Stretch::MinMax,
+            upscale: 8,
+            ..Default::default()
+        };
+        let frames = render_frames(&data, &options);
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].width, frames[1].width);
+        // The 40 px map is centred in the 43 px wide date strip
+        assert_eq!(frames[0].width, 43);
+        // Shared stretch, the same raw value has the same colour on both frames
+        assert_eq!(frames[0].get(1 + 2 * 8, 4 * 8), frames[1].get(1, 0));
+
+        let mut gif = Vec::new();
+        data.write_animation(&mut gif, AnimationFormat::Gif, &options, 500)
+            .unwrap();
+        assert_eq!(&gif[..6], b"GIF89a");
+
+        let mut apng = Vec::new();
+        data.write_animation(&mut apng, AnimationFormat::Apng, &options, 500)
+            .unwrap();
+        assert!(apng.windows(4).any(|w| w == b"acTL"));
+
+        // Without upscaling the map is 5 px wide but the full date still fits
+        let small = render_frames(&data, &RenderOptions::default());
+        assert!(small[0].width >= RgbaImage::text_width("2024-08-04", 1));
+
+        let huge = RgbaImage::new(70_000, 1, WHITE);
+        assert!(write_gif(&[huge], 500, Vec::new()).is_err());
+    }
+}
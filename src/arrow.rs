@@ -0,0 +1,185 @@
+// Apache Arrow / Parquet output (feature = "arrow")
+// One row per pixel per date, same ordering as ModisData::long_rows
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::Arc;
+
+use arrow_array::{Date32Array, Float64Array, Int32Array, RecordBatch, StringArray, UInt32Array};
+use arrow_schema::{DataType, Field, Schema};
+use chrono::NaiveDate;
+use parquet::arrow::ArrowWriter;
+use parquet::file::metadata::KeyValue;
+use parquet::file::properties::WriterProperties;
+
+use crate::structs::ModisData;
+
+/// Schema of the batches produced by [`ModisData::to_record_batch`]
+pub fn schema() -> Schema {
+    Schema::new(vec![
+        Field::new("site_id", DataType::Utf8, false),
+        Field::new("date", DataType::Date32, false),
+        Field::new("row", DataType::UInt32, false),
+        Field::new("col", DataType::UInt32, false),
+        Field::new("band", DataType::Utf8, false),
+        Field::new("value", DataType::Int32, false),
+        Field::new("scaled_value", DataType::Float64, true),
+        Field::new("qc", DataType::Int32, true),
+    ])
+}
+
+fn days_since_epoch(date: NaiveDate) -> i32 {
+    (date - NaiveDate::from_ymd_opt(1970, 1, 1).unwrap()).num_days() as i32
+}
+
+impl ModisData {
+    /// Product/band metadata, stored in the Parquet footer and on the Arrow schema
+    pub fn arrow_metadata(&self) -> HashMap<String, String> {
+        let mut metadata = HashMap::new();
+        metadata.insert(
+            "product".to_string(),
+            self.product().unwrap_or("").to_string(),
+        );
+        metadata.insert("band".to_string(), self.band.clone());
+        metadata.insert("units".to_string(), self.units.clone());
+        metadata.insert("scale".to_string(), self.scale.clone());
+        metadata.insert("xllcorner".to_string(), self.xllcorner.clone());
+        metadata.insert("yllcorner".to_string(), self.yllcorner.clone());
+        metadata.insert("cellsize".to_string(), self.cellsize.to_string());
+        metadata.insert("nrows".to_string(), self.nrows.to_string());
+        metadata.insert("ncols".to_string(), self.ncols.to_string());
+        metadata.insert("latitude".to_string(), self.latitude.to_string());
+        metadata.insert("longitude".to_string(), self.longitude.to_string());
+        metadata.insert("header".to_string(), self.header.clone());
+        metadata
+    }
+
+    /// Convert to an Arrow RecordBatch
+    ///
+    /// `qc` is an optional QC band for the same request (same grid and dates),
+    /// QC values are matched by date and pixel and are null where there is no match.
+    pub fn to_record_batch(
+        &self,
+        site_id: &str,
+        qc: Option<&ModisData>,
+    ) -> Result<RecordBatch, Box<dyn std::error::Error>> {
+        let ncols = self.ncols.max(1) as usize;
+        let mask = self.mask();
+
+        let mut dates = Vec::new();
+        let mut rows = Vec::new();
+        let mut cols = Vec::new();
+        let mut bands = Vec::new();
+        let mut values = Vec::new();
+        let mut scaled = Vec::new();
+        let mut qcs = Vec::new();
+
+        for subset in &self.subset {
+            let date = subset
+                .date()
+                .ok_or_else(|| format!("invalid date {:?}", subset.modis_date))?;
+            let qc_data = qc.and_then(|qc| {
+                qc.subset
+                    .iter()
+                    .find(|s| s.modis_date == subset.modis_date)
+                    .map(|s| &s.data)
+            });
+
+            for (i, value) in subset.data.iter().enumerate() {
+                dates.push(days_since_epoch(date.date()));
+                rows.push((i / ncols) as u32);
+                cols.push((i % ncols) as u32);
+                bands.push(subset.band.as_str());
+                values.push(*value);
+                scaled.push(self.masked_value(*value, &mask));
+                qcs.push(qc_data.and_then(|d| d.get(i).copied()));
+            }
+        }
+
+        let n = values.len();
+        let schema = schema().with_metadata(self.arrow_metadata());
+        let batch = RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                Arc::new(StringArray::from(vec![site_id; n])),
+                Arc::new(Date32Array::from(dates)),
+                Arc::new(UInt32Array::from(rows)),
+                Arc::new(UInt32Array::from(cols)),
+                Arc::new(StringArray::from(bands)),
+                Arc::new(Int32Array::from(values)),
+                Arc::new(Float64Array::from(scaled)),
+                Arc::new(Int32Array::from(qcs)),
+            ],
+        )?;
+        Ok(batch)
+    }
+
+    /// Write as Parquet, product/band metadata goes in the file footer
+    pub fn write_parquet<W: Write + Send>(
+        &self,
+        writer: W,
+        site_id: &str,
+        qc: Option<&ModisData>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let batch = self.to_record_batch(site_id, qc)?;
+
+        let mut metadata: Vec<KeyValue> = self
+            .arrow_metadata()
+            .into_iter()
+            .map(|(k, v)| KeyValue::new(k, v))
+            .collect();
+        metadata.sort_by(|a, b| a.key.cmp(&b.key));
+
+        let props = WriterProperties::builder()
+            .set_key_value_metadata(Some(metadata))
+            .build();
+        let mut writer = ArrowWriter::try_new(writer, batch.schema(), Some(props))?;
+        writer.write(&batch)?;
+        writer.close()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::{Array, Float64Array};
+    use bytes::Bytes;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    use crate::tests::otago;
+
+    #[test]
+    fn test_parquet_round_trip() {
+        let data = otago();
+        let mut qc = otago();
+        qc.subset[0].data = vec![0; 25];
+
+        let batch = data.to_record_batch("otago", Some(&qc)).unwrap();
+        assert_eq!(batch.num_rows(), 25);
+        assert_eq!(batch.num_columns(), 8);
+        // Fill codes (254 at pixel 8) are null, not 25.4
+        let scaled = batch
+            .column(6)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert!((scaled.value(0) - 1.3).abs() < 1e-9);
+        assert!(scaled.is_null(8));
+
+        let mut out = Vec::new();
+        data.write_parquet(&mut out, "otago", Some(&qc)).unwrap();
+
+        let builder = ParquetRecordBatchReaderBuilder::try_new(Bytes::from(out)).unwrap();
+        let footer = builder
+            .metadata()
+            .file_metadata()
+            .key_value_metadata()
+            .unwrap();
+        assert!(footer
+            .iter()
+            .any(|kv| kv.key == "product" && kv.value.as_deref() == Some("MCD15A2H")));
+
+        let batches: Vec<_> = builder.build().unwrap().map(|b| b.unwrap()).collect();
+        assert_eq!(batches[0].num_rows(), 25);
+    }
+}
@@ -0,0 +1,246 @@
+// ORNL Global Subset Tool CSV/ASCII downloads
+//
+// A header block of "key value" lines (ncols, nrows, xllcorner, yllcorner, cellsize, ...)
+// followed by one comma separated row per date:
+// MOD15A2H.A2024217.h29v13.061.2024228030354,MOD15A2H,A2024217,Lat-45.8667Lon170.6667Samp5Line5,2024228030354,Lai_500m,13,13,9,...
+
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::path::Path;
+
+use crate::dates::ModisDate;
+use crate::structs::{ModisData, Subset};
+use crate::BASE_URL;
+
+struct Location {
+    latitude: f64,
+    longitude: f64,
+    ncols: i32,
+    nrows: i32,
+}
+
+// Lat-45.8667Lon170.6667Samp5Line5
+fn parse_location(s: &str) -> Option<Location> {
+    let s = s.trim().strip_prefix("Lat")?;
+    let (latitude, s) = s.split_once("Lon")?;
+    let (longitude, s) = s.split_once("Samp")?;
+    let (ncols, nrows) = s.split_once("Line")?;
+    Some(Location {
+        latitude: latitude.parse().ok()?,
+        longitude: longitude.parse().ok()?,
+        ncols: ncols.parse().ok()?,
+        nrows: nrows.parse().ok()?,
+    })
+}
+
+// h29v13 from the HDF name
+fn parse_tile(hdf_name: &str) -> String {
+    hdf_name
+        .split('.')
+        .find(|part| {
+            let b = part.as_bytes();
+            b.len() == 6 && b[0] == b'h' && b[3] == b'v' && part[1..3].parse::<u8>().is_ok()
+        })
+        .unwrap_or("")
+        .to_string()
+}
+
+/// Parse an ORNL CSV/ASCII subset download into a ModisData
+///
+/// `header` is set to the equivalent REST subset URL so both sources behave the same
+/// (e.g. [`ModisData::product`]).
+pub fn read_ascii<R: BufRead>(reader: R) -> Result<ModisData, Box<dyn std::error::Error>> {
+    let mut meta: HashMap<String, String> = HashMap::new();
+    let mut location = None;
+    let mut product = None;
+    let mut subset = Vec::new();
+
+    for (line_no, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+
+        // Data row
+        if fields.len() > 6 && ModisDate::parse(fields[2]).is_some() {
+            let mut data = Vec::with_capacity(fields.len() - 6);
+            for value in &fields[6..] {
+                data.push(value.parse::<i32>().map_err(|_| {
+                    format!("line {}: invalid pixel value {:?}", line_no + 1, value)
+                })?);
+            }
+
+            if location.is_none() {
+                location = parse_location(fields[3]);
+            }
+            if product.is_none() {
+                product = Some(fields[1].to_string());
+            }
+
+            let date = ModisDate::parse(fields[2]).unwrap();
+            subset.push(Subset {
+                modis_date: fields[2].to_string(),
+                calendar_date: date.calendar_date(),
+                band: fields[5].to_string(),
+                tile: parse_tile(fields[0]),
+                proc_date: fields[4].to_string(),
+                data,
+            });
+            continue;
+        }
+
+        // Header, "key value", "key,value" or "key: value"
+        let (key, value) =
+            match line.split_once(|c: char| c == ',' || c == ':' || c.is_whitespace()) {
+                Some((key, value)) => (key, value.trim_start_matches([',', ':']).trim()),
+                None => continue,
+            };
+        meta.insert(key.to_lowercase(), value.to_string());
+    }
+
+    if subset.is_empty() {
+        return Err("no data rows found".into());
+    }
+
+    let get = |key: &str| meta.get(key).map(|s| s.as_str());
+    let number = |key: &str| -> Result<Option<f64>, Box<dyn std::error::Error>> {
+        match get(key) {
+            Some(v) => {
+                Ok(Some(v.parse::<f64>().map_err(|_| {
+                    format!("invalid {} in header: {:?}", key, v)
+                })?))
+            }
+            None => Ok(None),
+        }
+    };
+
+    let nrows = match number("nrows")? {
+        Some(n) => n as i32,
+        None => location.as_ref().map(|l| l.nrows).ok_or("missing nrows")?,
+    };
+    let ncols = match number("ncols")? {
+        Some(n) => n as i32,
+        None => location.as_ref().map(|l| l.ncols).ok_or("missing ncols")?,
+    };
+    let cellsize = number("cellsize")?.ok_or("missing cellsize")?;
+    let xllcorner = get("xllcorner").ok_or("missing xllcorner")?.to_string();
+    let yllcorner = get("yllcorner").ok_or("missing yllcorner")?.to_string();
+
+    let cells = match nrows.checked_mul(ncols) {
+        Some(n) if nrows >= 0 && ncols >= 0 => n as usize,
+        _ => return Err(format!("invalid grid size {} rows x {} cols", nrows, ncols).into()),
+    };
+    if let Some(s) = subset.iter().find(|s| s.data.len() != cells) {
+        return Err(format!(
+            "{} has {} values, expected {} ({} rows x {} cols)",
+            s.modis_date,
+            s.data.len(),
+            cells,
+            nrows,
+            ncols
+        )
+        .into());
+    }
+
+    let latitude = match number("latitude")? {
+        Some(v) => v,
+        None => location
+            .as_ref()
+            .map(|l| l.latitude)
+            .ok_or("missing latitude")?,
+    };
+    let longitude = match number("longitude")? {
+        Some(v) => v,
+        None => location
+            .as_ref()
+            .map(|l| l.longitude)
+            .ok_or("missing longitude")?,
+    };
+
+    let product = get("product")
+        .map(|s| s.to_string())
+        .or(product)
+        .unwrap_or_default();
+    let band = get("band")
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| subset[0].band.clone());
+
+    let header = format!(
+        "{}{}/subset?latitude={}&longitude={}&band={}&startDate={}&endDate={}",
+        BASE_URL,
+        product,
+        latitude,
+        longitude,
+        band,
+        subset[0].modis_date,
+        subset[subset.len() - 1].modis_date
+    );
+
+    Ok(ModisData {
+        xllcorner,
+        yllcorner,
+        cellsize,
+        nrows,
+        ncols,
+        band,
+        units: get("units").unwrap_or("").to_string(),
+        scale: get("scale").unwrap_or("").to_string(),
+        latitude,
+        longitude,
+        header,
+        subset,
+    })
+}
+
+impl ModisData {
+    /// Read an ORNL CSV/ASCII subset download from disk, see [`read_ascii`]
+    pub fn from_ascii_path<P: AsRef<Path>>(
+        path: P,
+    ) -> Result<ModisData, Box<dyn std::error::Error>> {
+        let file = std::fs::File::open(path)?;
+        read_ascii(std::io::BufReader::new(file))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ASCII: &str = "ncols 5
+nrows 5
+xllcorner 13213215.26
+yllcorner -5101536.36
+cellsize 463.312716528
+scale 0.1
+units m^2/m^2
+HDFname,Product,Date,Location,Processed_Date,Subset_Data
+MCD15A2H.A2024209.h29v13.061.2024220031712,MCD15A2H,A2024209,Lat-45.8667Lon170.6667Samp5Line5,2024220031712,Lai_500m,12,13,9,9,6,10,9,4,254,254,11,1,254,3,9,5,5,5,9,11,1,1,18,254,254
+MCD15A2H.A2024217.h29v13.061.2024228030354,MCD15A2H,A2024217,Lat-45.8667Lon170.6667Samp5Line5,2024228030354,Lai_500m,13,13,9,9,6,10,9,4,254,254,11,1,254,3,9,5,5,5,9,11,1,1,18,254,254
+";
+
+    #[test]
+    fn test_read_ascii() {
+        let data = read_ascii(ASCII.as_bytes()).unwrap();
+        let otago = crate::tests::otago();
+        assert_eq!(data.nrows, 5);
+        assert_eq!(data.ncols, 5);
+        assert_eq!(data.xll(), otago.xll());
+        assert_eq!(data.product(), Some("MCD15A2H"));
+        assert_eq!(data.scale_factor(), Some(0.1));
+        assert_eq!(data.subset.len(), 2);
+        assert_eq!(data.subset[1].calendar_date, "2024-08-04");
+        assert_eq!(data.subset[1].tile, "h29v13");
+        assert_eq!(data.subset[1].data, otago.subset[0].data);
+    }
+
+    #[test]
+    fn test_read_ascii_bad_length() {
+        let text = ASCII.replace(",254,254\nMCD15A2H.A2024217", "\nMCD15A2H.A2024217");
+        assert!(read_ascii(text.as_bytes()).is_err());
+        let text = ASCII.replace("ncols 5\nnrows 5", "ncols 100000\nnrows 100000");
+        assert!(read_ascii(text.as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,101 @@
+// MODIS dates are "AYYYYDDD" (year and day of year), calendar dates are "YYYY-MM-DD"
+
+use chrono::{Datelike, NaiveDate};
+use serde::{Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::structs::Subset;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ModisDate(pub NaiveDate);
+
+impl ModisDate {
+    pub fn from_year_doy(year: i32, doy: u32) -> Option<ModisDate> {
+        NaiveDate::from_yo_opt(year, doy).map(ModisDate)
+    }
+
+    /// Parse either "A2024217" or "2024-08-04"
+    pub fn parse(s: &str) -> Option<ModisDate> {
+        let s = s.trim();
+        if let Some(rest) = s.strip_prefix('A') {
+            if rest.len() != 7 {
+                return None;
+            }
+            let year = rest.get(..4)?.parse().ok()?;
+            let doy = rest.get(4..)?.parse().ok()?;
+            ModisDate::from_year_doy(year, doy)
+        } else {
+            NaiveDate::parse_from_str(s, "%Y-%m-%d").ok().map(ModisDate)
+        }
+    }
+
+    pub fn year(&self) -> i32 {
+        self.0.year()
+    }
+
+    pub fn doy(&self) -> u32 {
+        self.0.ordinal()
+    }
+
+    pub fn date(&self) -> NaiveDate {
+        self.0
+    }
+
+    /// "YYYY-MM-DD"
+    pub fn calendar_date(&self) -> String {
+        self.0.format("%Y-%m-%d").to_string()
+    }
+}
+
+impl fmt::Display for ModisDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "A{:04}{:03}", self.year(), self.doy())
+    }
+}
+
+impl FromStr for ModisDate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ModisDate::parse(s).ok_or_else(|| format!("invalid MODIS date: {}", s))
+    }
+}
+
+impl From<NaiveDate> for ModisDate {
+    fn from(date: NaiveDate) -> Self {
+        ModisDate(date)
+    }
+}
+
+impl Serialize for ModisDate {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl Subset {
+    /// Parsed `modis_date`, falling back to `calendar_date`
+    pub fn date(&self) -> Option<ModisDate> {
+        ModisDate::parse(&self.modis_date).or_else(|| ModisDate::parse(&self.calendar_date))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let date = ModisDate::parse("A2024217").unwrap();
+        assert_eq!(date.calendar_date(), "2024-08-04");
+        assert_eq!(date.to_string(), "A2024217");
+        assert_eq!(ModisDate::parse("2024-08-04"), Some(date));
+        assert_eq!(ModisDate::parse("A2023366"), None);
+        // 7 bytes but not on a char boundary
+        assert_eq!(ModisDate::parse("A12é345"), None);
+    }
+}
@@ -0,0 +1,103 @@
+// Tidy (long format) export, one row per pixel per date
+// Column names match the ORNL download where they overlap so the output
+// can be read directly by pandas, R and DuckDB
+
+use serde::Serialize;
+use std::io::Write;
+
+use crate::structs::ModisData;
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct LongRow<'a> {
+    pub product: &'a str,
+    pub band: &'a str,
+    pub modis_date: &'a str,
+    pub calendar_date: &'a str,
+    pub tile: &'a str,
+    pub proc_date: &'a str,
+    pub row: usize,
+    pub col: usize,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub value: i32,
+    /// None (an empty CSV field) for fill and out of range values
+    pub scaled_value: Option<f64>,
+}
+
+impl ModisData {
+    /// Flatten all subsets into long format rows, ordered by date then row then column
+    pub fn long_rows(&self) -> impl Iterator<Item = LongRow<'_>> + '_ {
+        let ncols = self.ncols.max(1) as usize;
+        let product = self.product().unwrap_or("");
+        let mask = self.mask();
+
+        self.subset.iter().flat_map(move |subset| {
+            let mask = mask.clone();
+            subset.data.iter().enumerate().map(move |(i, value)| {
+                let (latitude, longitude) = self.pixel_lat_lon(i / ncols, i % ncols);
+                LongRow {
+                    product,
+                    band: &subset.band,
+                    modis_date: &subset.modis_date,
+                    calendar_date: &subset.calendar_date,
+                    tile: &subset.tile,
+                    proc_date: &subset.proc_date,
+                    row: i / ncols,
+                    col: i % ncols,
+                    latitude,
+                    longitude,
+                    value: *value,
+                    scaled_value: self.masked_value(*value, &mask),
+                }
+            })
+        })
+    }
+
+    /// Write the long format rows as CSV (with a header row)
+    pub fn write_csv<W: Write>(&self, writer: W) -> Result<(), Box<dyn std::error::Error>> {
+        let mut writer = csv::Writer::from_writer(writer);
+        for row in self.long_rows() {
+            writer.serialize(row)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::otago;
+
+    #[test]
+    fn test_write_csv() {
+        let data = otago();
+        let mut out = Vec::new();
+        data.write_csv(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let mut lines = out.lines();
+        assert_eq!(
+            lines.next().unwrap(),
+            "product,band,modis_date,calendar_date,tile,proc_date,row,col,latitude,longitude,value,scaled_value"
+        );
+        let first: Vec<&str> = lines.next().unwrap().split(',').collect();
+        assert_eq!(
+            &first[..8],
+            &[
+                "MCD15A2H",
+                "Lai_500m",
+                "A2024217",
+                "2024-08-04",
+                "h29v13",
+                "2024228030354",
+                "0",
+                "0"
+            ]
+        );
+        assert_eq!(first[10], "13");
+        assert!((first[11].parse::<f64>().unwrap() - 1.3).abs() < 1e-9);
+        // 254 (water) is a fill code, not 25.4
+        let water: Vec<&str> = lines.nth(7).unwrap().split(',').collect();
+        assert_eq!((water[10], water[11]), ("254", ""));
+        assert_eq!(lines.count(), 16);
+    }
+}
@@ -0,0 +1,370 @@
+// Burned area (MCD64A1) and active fire (MOD14A2/MYD14A2) decoding
+//
+// MCD64A1 is monthly, Burn_Date holds the day of year of the burn within the composite's
+// year, 0 for unburned, -1 for unmapped (too little data) and -2 for water.
+// First_Day/Last_Day are the days of year bounding when the burn could have been detected.
+
+use std::collections::BTreeMap;
+
+use chrono::{Datelike, NaiveDate};
+use serde::Serialize;
+
+use crate::dates::ModisDate;
+use crate::stats::Window;
+use crate::structs::{ModisData, Subset};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum BurnStatus {
+    Unburned,
+    Burned(NaiveDate),
+    Unmapped,
+    Water,
+}
+
+impl BurnStatus {
+    /// Decode a Burn_Date value from a composite of `year`, None for values outside 0-366
+    pub fn decode(value: i32, year: i32) -> Option<BurnStatus> {
+        match value {
+            0 => Some(BurnStatus::Unburned),
+            -1 => Some(BurnStatus::Unmapped),
+            -2 => Some(BurnStatus::Water),
+            1..=366 => {
+                ModisDate::from_year_doy(year, value as u32).map(|d| BurnStatus::Burned(d.date()))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn is_mapped(&self) -> bool {
+        matches!(self, BurnStatus::Unburned | BurnStatus::Burned(_))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BurnEvent {
+    pub row: usize,
+    pub col: usize,
+    /// Composite (month) the burn was mapped in
+    pub composite: ModisDate,
+    pub date: NaiveDate,
+    /// Burn_Date_Uncertainty, in days
+    pub uncertainty_days: Option<i32>,
+    pub first_day: Option<NaiveDate>,
+    pub last_day: Option<NaiveDate>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+pub enum FireConfidence {
+    Low,
+    Nominal,
+    High,
+}
+
+/// MOD14A2/MYD14A2 FireMask classes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum FireClass {
+    /// 0, missing input data
+    NotProcessedMissing,
+    /// 1, no longer used
+    NotProcessedObsolete,
+    /// 2, other reason
+    NotProcessedOther,
+    NonFireWater,
+    Cloud,
+    NonFireLand,
+    Unknown,
+    Fire(FireConfidence),
+}
+
+impl FireClass {
+    pub fn decode(value: i32) -> Option<FireClass> {
+        Some(match value {
+            0 => FireClass::NotProcessedMissing,
+            1 => FireClass::NotProcessedObsolete,
+            2 => FireClass::NotProcessedOther,
+            3 => FireClass::NonFireWater,
+            4 => FireClass::Cloud,
+            5 => FireClass::NonFireLand,
+            6 => FireClass::Unknown,
+            7 => FireClass::Fire(FireConfidence::Low),
+            8 => FireClass::Fire(FireConfidence::Nominal),
+            9 => FireClass::Fire(FireConfidence::High),
+            _ => return None,
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FireClass::NotProcessedMissing => "not processed (missing input data)",
+            FireClass::NotProcessedObsolete => "not processed (obsolete)",
+            FireClass::NotProcessedOther => "not processed (other reason)",
+            FireClass::NonFireWater => "non-fire water",
+            FireClass::Cloud => "cloud",
+            FireClass::NonFireLand => "non-fire land",
+            FireClass::Unknown => "unknown",
+            FireClass::Fire(FireConfidence::Low) => "fire (low confidence)",
+            FireClass::Fire(FireConfidence::Nominal) => "fire (nominal confidence)",
+            FireClass::Fire(FireConfidence::High) => "fire (high confidence)",
+        }
+    }
+
+    pub fn confidence(&self) -> Option<FireConfidence> {
+        match self {
+            FireClass::Fire(c) => Some(*c),
+            _ => None,
+        }
+    }
+
+    pub fn is_fire(&self) -> bool {
+        self.confidence().is_some()
+    }
+}
+
+/// FireMask classes of a subset, None for values outside 0-9
+pub fn decode_fire_mask(subset: &Subset) -> Vec<Option<FireClass>> {
+    subset.data.iter().map(|v| FireClass::decode(*v)).collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BurnedFraction {
+    pub year: i32,
+    /// None for the annual summary
+    pub month: Option<u32>,
+    pub pixels: usize,
+    /// Pixels mapped (burned or unburned), for a year those mapped in any month
+    pub mapped: usize,
+    pub burned: usize,
+    /// burned / mapped
+    pub fraction: Option<f64>,
+}
+
+impl BurnedFraction {
+    fn new(year: i32, month: Option<u32>, pixels: usize, mapped: usize, burned: usize) -> Self {
+        BurnedFraction {
+            year,
+            month,
+            pixels,
+            mapped,
+            burned,
+            fraction: (mapped > 0).then(|| burned as f64 / mapped as f64),
+        }
+    }
+}
+
+impl ModisData {
+    // (composite date, decoded Burn_Date) for each subset, in date order
+    fn burn_statuses(&self) -> Vec<(ModisDate, Vec<Option<BurnStatus>>)> {
+        let mut out: Vec<_> = self
+            .subset
+            .iter()
+            .filter_map(|s| {
+                let date = s.date()?;
+                let statuses = s
+                    .data
+                    .iter()
+                    .map(|v| BurnStatus::decode(*v, date.year()))
+                    .collect();
+                Some((date, statuses))
+            })
+            .collect();
+        out.sort_by_key(|(date, _)| *date);
+        out
+    }
+
+    /// Burn events from a Burn_Date layer, with the uncertainty and first/last day layers
+    /// for the same request when given
+    pub fn burn_events(
+        &self,
+        uncertainty: Option<&ModisData>,
+        first_day: Option<&ModisData>,
+        last_day: Option<&ModisData>,
+    ) -> Vec<BurnEvent> {
+        let ncols = self.ncols.max(1) as usize;
+        let lookup = |layer: Option<&ModisData>, modis_date: &str, i: usize| {
+            layer?
+                .subset
+                .iter()
+                .find(|s| s.modis_date == modis_date)?
+                .data
+                .get(i)
+                .copied()
+        };
+        let mut events = Vec::new();
+        for (composite, statuses) in self.burn_statuses() {
+            let modis_date = composite.to_string();
+            let day = |layer, i| {
+                lookup(layer, &modis_date, i)
+                    .filter(|d| (1..=366).contains(d))
+                    .and_then(|d| ModisDate::from_year_doy(composite.year(), d as u32))
+                    .map(|d| d.date())
+            };
+            for (i, status) in statuses.iter().enumerate() {
+                if let Some(BurnStatus::Burned(date)) = status {
+                    events.push(BurnEvent {
+                        row: i / ncols,
+                        col: i % ncols,
+                        composite,
+                        date: *date,
+                        uncertainty_days: lookup(uncertainty, &modis_date, i).filter(|u| *u >= 0),
+                        first_day: day(first_day, i),
+                        last_day: day(last_day, i),
+                    });
+                }
+            }
+        }
+        events
+    }
+
+    /// Burned fraction of the window for each monthly composite
+    pub fn burned_fraction_monthly(&self, window: Window) -> Vec<BurnedFraction> {
+        let indices = self.window_indices(window);
+        self.burn_statuses()
+            .into_iter()
+            .map(|(date, statuses)| {
+                let window: Vec<&Option<BurnStatus>> =
+                    indices.iter().filter_map(|i| statuses.get(*i)).collect();
+                let mapped = window.iter().filter(|s| s.is_some_and(|s| s.is_mapped()));
+                let burned = window
+                    .iter()
+                    .filter(|s| matches!(s, Some(BurnStatus::Burned(_))))
+                    .count();
+                BurnedFraction::new(
+                    date.year(),
+                    Some(date.date().month()),
+                    indices.len(),
+                    mapped.count(),
+                    burned,
+                )
+            })
+            .collect()
+    }
+
+    /// Burned fraction of the window for each year, a pixel counts once however often it burns
+    pub fn burned_fraction_annual(&self, window: Window) -> Vec<BurnedFraction> {
+        let indices = self.window_indices(window);
+        // year -> (mapped, burned) per window pixel
+        let mut years: BTreeMap<i32, Vec<(bool, bool)>> = BTreeMap::new();
+        for (date, statuses) in self.burn_statuses() {
+            let flags = years
+                .entry(date.year())
+                .or_insert_with(|| vec![(false, false); indices.len()]);
+            for (flag, i) in flags.iter_mut().zip(&indices) {
+                if let Some(Some(status)) = statuses.get(*i) {
+                    flag.0 |= status.is_mapped();
+                    flag.1 |= matches!(status, BurnStatus::Burned(_));
+                }
+            }
+        }
+        years
+            .into_iter()
+            .map(|(year, flags)| {
+                let mapped = flags.iter().filter(|f| f.0).count();
+                let burned = flags.iter().filter(|f| f.1).count();
+                BurnedFraction::new(year, None, indices.len(), mapped, burned)
+            })
+            .collect()
+    }
+
+    /// Earliest burn date of each pixel (row-major) across all composites
+    pub fn first_burn_dates(&self) -> Vec<Option<NaiveDate>> {
+        let n = (self.nrows.max(0) * self.ncols.max(0)) as usize;
+        let mut first = vec![None; n];
+        for (_, statuses) in self.burn_statuses() {
+            for (f, status) in first.iter_mut().zip(statuses) {
+                if let (None, Some(BurnStatus::Burned(date))) = (*f, status) {
+                    *f = Some(date);
+                }
+            }
+        }
+        first
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::otago;
+
+    fn burn_date() -> ModisData {
+        let mut data = otago();
+        data.band = "Burn_Date".to_string();
+        data.scale = "0".to_string();
+        let mut months = Vec::new();
+        for (modis_date, calendar_date, burned) in [
+            ("A2023335", "2023-12-01", vec![(0, 340)]),
+            ("A2024001", "2024-01-01", vec![(0, 5), (1, 20)]),
+            ("A2024032", "2024-02-01", vec![(1, 45), (2, 40)]),
+        ] {
+            let mut s = data.subset[0].clone();
+            s.modis_date = modis_date.to_string();
+            s.calendar_date = calendar_date.to_string();
+            s.data = vec![0; 25];
+            s.data[24] = -2;
+            s.data[23] = -1;
+            for (i, doy) in burned {
+                s.data[i] = doy;
+            }
+            months.push(s);
+        }
+        data.subset = months;
+        data
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(
+            BurnStatus::decode(60, 2024),
+            Some(BurnStatus::Burned(
+                NaiveDate::from_ymd_opt(2024, 2, 29).unwrap()
+            ))
+        );
+        assert_eq!(BurnStatus::decode(-2, 2024), Some(BurnStatus::Water));
+        assert_eq!(BurnStatus::decode(400, 2024), None);
+        assert_eq!(
+            FireClass::decode(8).and_then(|c| c.confidence()),
+            Some(FireConfidence::Nominal)
+        );
+        assert!(!FireClass::decode(5).unwrap().is_fire());
+        assert_eq!(FireClass::decode(10), None);
+    }
+
+    #[test]
+    fn test_burn_summaries() {
+        let data = burn_date();
+        let mut uncertainty = data.clone();
+        for s in uncertainty.subset.iter_mut() {
+            s.data = vec![3; 25];
+        }
+
+        let events = data.burn_events(Some(&uncertainty), None, None);
+        assert_eq!(events.len(), 5);
+        assert_eq!(
+            events[0].date,
+            NaiveDate::from_ymd_opt(2023, 12, 6).unwrap()
+        );
+        assert_eq!(events[1].composite.to_string(), "A2024001");
+        assert_eq!(events[1].uncertainty_days, Some(3));
+
+        let monthly = data.burned_fraction_monthly(Window::All);
+        assert_eq!(monthly.len(), 3);
+        assert_eq!(monthly[1].month, Some(1));
+        assert_eq!(monthly[1].mapped, 23);
+        assert_eq!(monthly[1].burned, 2);
+        assert_eq!(monthly[2].burned, 2);
+
+        let annual = data.burned_fraction_annual(Window::All);
+        assert_eq!(annual.len(), 2);
+        assert_eq!(annual[1].year, 2024);
+        // Pixel 1 burned in both 2024 composites but counts once, 2 + 2 monthly burns are 3 pixels
+        assert_eq!(annual[1].burned, 3);
+        assert!((annual[1].fraction.unwrap() - 3.0 / 23.0).abs() < 1e-9);
+
+        let first = data.first_burn_dates();
+        assert_eq!(
+            first[0],
+            Some(NaiveDate::from_ymd_opt(2023, 12, 6).unwrap())
+        );
+        assert_eq!(first[2], Some(NaiveDate::from_ymd_opt(2024, 2, 9).unwrap()));
+        assert_eq!(first[3], None);
+    }
+}
@@ -0,0 +1,121 @@
+// GeoJSON export of pixel footprints (RFC 7946, WGS84 lon/lat)
+
+use serde_json::{json, Map, Value};
+
+use crate::structs::ModisData;
+
+impl ModisData {
+    /// FeatureCollection with one polygon per pixel
+    ///
+    /// With `date` (modis or calendar date) each feature carries that date's `value` and
+    /// `scaled_value` (null for fill and out of range values), otherwise the raw value for
+    /// every date keyed by calendar date.
+    /// `segments` is the number of points per pixel edge, 1 gives plain quadrilaterals.
+    /// Errors when `date` is not in the subset.
+    pub fn to_geojson(
+        &self,
+        date: Option<&str>,
+        segments: usize,
+    ) -> Result<Value, Box<dyn std::error::Error>> {
+        let subsets: Vec<_> = match date {
+            Some(date) => self
+                .subset
+                .iter()
+                .filter(|s| s.modis_date == date || s.calendar_date == date)
+                .collect(),
+            None => self.subset.iter().collect(),
+        };
+        if let (Some(date), true) = (date, subsets.is_empty()) {
+            return Err(format!("no subset for date {}", date).into());
+        }
+
+        let nrows = self.nrows.max(0) as usize;
+        let ncols = self.ncols.max(0) as usize;
+        let mask = self.mask();
+        let mut features = Vec::with_capacity(nrows * ncols);
+
+        for row in 0..nrows {
+            for col in 0..ncols {
+                let i = row * ncols + col;
+                let ring: Vec<Value> = self
+                    .pixel_footprint(row, col, segments)
+                    .into_iter()
+                    .map(|(lat, lon)| json!([lon, lat]))
+                    .collect();
+
+                let mut properties = Map::new();
+                properties.insert("row".to_string(), json!(row));
+                properties.insert("col".to_string(), json!(col));
+                properties.insert(
+                    "tile".to_string(),
+                    json!(subsets.first().map(|s| s.tile.as_str())),
+                );
+                properties.insert("band".to_string(), json!(self.band));
+
+                match (date, subsets.first()) {
+                    (Some(_), Some(subset)) => {
+                        let value = subset.data.get(i).copied();
+                        properties.insert("modis_date".to_string(), json!(subset.modis_date));
+                        properties.insert("calendar_date".to_string(), json!(subset.calendar_date));
+                        properties.insert("value".to_string(), json!(value));
+                        properties.insert(
+                            "scaled_value".to_string(),
+                            json!(value.and_then(|v| self.masked_value(v, &mask))),
+                        );
+                    }
+                    _ => {
+                        for subset in &subsets {
+                            properties
+                                .insert(subset.calendar_date.clone(), json!(subset.data.get(i)));
+                        }
+                    }
+                }
+
+                features.push(json!({
+                    "type": "Feature",
+                    "id": i,
+                    "geometry": {
+                        "type": "Polygon",
+                        "coordinates": [ring],
+                    },
+                    "properties": properties,
+                }));
+            }
+        }
+
+        Ok(json!({
+            "type": "FeatureCollection",
+            "features": features,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::otago;
+
+    #[test]
+    fn test_to_geojson() {
+        let data = otago();
+        let geojson = data.to_geojson(Some("2024-08-04"), 4).unwrap();
+        let features = geojson["features"].as_array().unwrap();
+        assert_eq!(features.len(), 25);
+
+        let ring = features[0]["geometry"]["coordinates"][0]
+            .as_array()
+            .unwrap();
+        assert_eq!(ring.len(), 17);
+        assert_eq!(ring[0], ring[16]);
+        assert_eq!(features[0]["properties"]["value"], 13);
+        assert!((features[0]["properties"]["scaled_value"].as_f64().unwrap() - 1.3).abs() < 1e-9);
+        // 254 (water) is kept as the raw value but has no scaled value
+        assert_eq!(features[8]["properties"]["value"], 254);
+        assert!(features[8]["properties"]["scaled_value"].is_null());
+
+        let all = data.to_geojson(None, 1).unwrap();
+        assert_eq!(all["features"][0]["properties"]["2024-08-04"], 13);
+        let by_modis_date = data.to_geojson(Some("A2024217"), 1).unwrap();
+        assert_eq!(by_modis_date["features"][0]["properties"]["value"], 13);
+        assert!(data.to_geojson(Some("2024-08-05"), 1).is_err());
+    }
+}
@@ -0,0 +1,152 @@
+// Geometry helpers for the ModisData raster
+// The subset grid is row-major, row 0 is the northern-most row and
+// (xllcorner, yllcorner) is the lower left corner in sinusoidal metres
+
+use crate::sinusoidal;
+use crate::structs::{ModisData, ProductType};
+
+impl ModisData {
+    /// Lower left x corner in sinusoidal metres
+    pub fn xll(&self) -> f64 {
+        self.xllcorner.trim().parse().unwrap_or(f64::NAN)
+    }
+
+    /// Lower left y corner in sinusoidal metres
+    pub fn yll(&self) -> f64 {
+        self.yllcorner.trim().parse().unwrap_or(f64::NAN)
+    }
+
+    /// Scale factor for the band, None if the API reports none (or 0)
+    pub fn scale_factor(&self) -> Option<f64> {
+        match self.scale.trim().parse::<f64>() {
+            Ok(scale) if scale != 0.0 && scale.is_finite() => Some(scale),
+            _ => None,
+        }
+    }
+
+    /// Apply the scale factor to a raw value, raw value is returned if there is no scale
+    pub fn scale_value(&self, value: i32) -> f64 {
+        match self.scale_factor() {
+            Some(scale) => value as f64 * scale,
+            None => value as f64,
+        }
+    }
+
+    /// Product name, parsed from the request URL in `header`
+    pub fn product(&self) -> Option<&str> {
+        let path = self.header.split('?').next()?;
+        let mut parts = path.rsplit('/');
+        match (parts.next(), parts.next()) {
+            (Some("subset"), Some(product)) if !product.is_empty() => Some(product),
+            _ => None,
+        }
+    }
+
+    /// Sinusoidal x/y of the centre of the pixel at (row, col)
+    pub fn pixel_center(&self, row: usize, col: usize) -> (f64, f64) {
+        let x = self.xll() + (col as f64 + 0.5) * self.cellsize;
+        let y = self.yll() + (self.nrows as f64 - row as f64 - 0.5) * self.cellsize;
+        (x, y)
+    }
+
+    /// Latitude/longitude (degrees) of the centre of the pixel at (row, col)
+    pub fn pixel_lat_lon(&self, row: usize, col: usize) -> (f64, f64) {
+        let (x, y) = self.pixel_center(row, col);
+        sinusoidal::to_lat_lon(x, y)
+    }
+
+    /// (row, col) of the pixel containing the given sinusoidal x/y, None if outside the grid
+    pub fn pixel_at_xy(&self, x: f64, y: f64) -> Option<(usize, usize)> {
+        let col = ((x - self.xll()) / self.cellsize).floor();
+        let row_from_bottom = ((y - self.yll()) / self.cellsize).floor();
+        if col < 0.0
+            || row_from_bottom < 0.0
+            || col >= self.ncols as f64
+            || row_from_bottom >= self.nrows as f64
+        {
+            return None;
+        }
+        Some((
+            self.nrows as usize - 1 - row_from_bottom as usize,
+            col as usize,
+        ))
+    }
+
+    /// (row, col) of the pixel containing the given latitude/longitude, None if outside the grid
+    pub fn pixel_at(&self, latitude: f64, longitude: f64) -> Option<(usize, usize)> {
+        let (x, y) = sinusoidal::to_sinusoidal(latitude, longitude);
+        self.pixel_at_xy(x, y)
+    }
+}
+
+impl ModisData {
+    /// Sinusoidal x/y bounds of the pixel at (row, col): (xmin, ymin, xmax, ymax)
+    pub fn pixel_bounds(&self, row: usize, col: usize) -> (f64, f64, f64, f64) {
+        let xmin = self.xll() + col as f64 * self.cellsize;
+        let ymin = self.yll() + (self.nrows as f64 - row as f64 - 1.0) * self.cellsize;
+        (xmin, ymin, xmin + self.cellsize, ymin + self.cellsize)
+    }
+
+    /// Closed, counter-clockwise outline of the pixel in latitude/longitude
+    ///
+    /// Each edge is split into `segments` pieces before reprojecting, so the
+    /// curved sinusoidal edges are followed in geographic space.
+    pub fn pixel_footprint(&self, row: usize, col: usize, segments: usize) -> Vec<(f64, f64)> {
+        let segments = segments.max(1);
+        let (xmin, ymin, xmax, ymax) = self.pixel_bounds(row, col);
+        let corners = [(xmin, ymin), (xmax, ymin), (xmax, ymax), (xmin, ymax)];
+
+        let mut ring = Vec::with_capacity(segments * 4 + 1);
+        for i in 0..4 {
+            let (x0, y0) = corners[i];
+            let (x1, y1) = corners[(i + 1) % 4];
+            for s in 0..segments {
+                let t = s as f64 / segments as f64;
+                ring.push(sinusoidal::to_lat_lon(
+                    x0 + (x1 - x0) * t,
+                    y0 + (y1 - y0) * t,
+                ));
+            }
+        }
+        ring.push(ring[0]);
+        ring
+    }
+}
+
+impl ModisData {
+    /// Product type parsed from `header`
+    pub fn product_type(&self) -> Option<ProductType> {
+        self.product()?.parse().ok()
+    }
+
+    /// True if `other` is on exactly the same grid (corner, cell size and shape)
+    pub fn same_grid(&self, other: &ModisData) -> bool {
+        self.nrows == other.nrows
+            && self.ncols == other.ncols
+            && (self.cellsize - other.cellsize).abs() < 1e-6
+            && (self.xll() - other.xll()).abs() < 1e-3
+            && (self.yll() - other.yll()).abs() < 1e-3
+    }
+}
+
+/// Combine several fetches of the same grid and band (e.g. one request per year) into one
+/// multi-date ModisData, dates are sorted and duplicates keep the first occurrence
+pub fn stack(data: &[ModisData]) -> Result<ModisData, Box<dyn std::error::Error>> {
+    let first = data.first().ok_or("nothing to stack")?;
+    let mut out = first.clone();
+    for other in &data[1..] {
+        if !first.same_grid(other) {
+            return Err("cannot stack subsets on different grids".into());
+        }
+        if other.band != first.band {
+            return Err(format!("cannot stack bands {} and {}", first.band, other.band).into());
+        }
+        for subset in &other.subset {
+            if !out.subset.iter().any(|s| s.modis_date == subset.modis_date) {
+                out.subset.push(subset.clone());
+            }
+        }
+    }
+    out.subset.sort_by_key(|s| s.date());
+    Ok(out)
+}
@@ -0,0 +1,383 @@
+// Spectral indices from the surface reflectance products (MOD09A1, MYD09A1, VNP09A1, VNP09H1)
+//
+// Index grids are returned as ModisData scaled like MOD13Q1 (value x 10000, scale 0.0001)
+// so rendering, export and the time series tools work on them unchanged.
+
+use std::collections::HashMap;
+
+use crate::mask::QcFilter;
+use crate::structs::{ModisData, ProductType, Subset};
+
+/// Fill for masked index values, same as the reflectance bands
+pub const INDEX_FILL: i32 = -28672;
+const INDEX_SCALE: f64 = 10000.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SpectralBand {
+    Blue,
+    Green,
+    Red,
+    Nir,
+    /// ~1.6 um
+    Swir1,
+    /// ~2.1-2.2 um
+    Swir2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Wavelength {
+    pub band: &'static str,
+    pub spectral: SpectralBand,
+    /// Bandpass in nm
+    pub min_nm: f64,
+    pub max_nm: f64,
+}
+
+const fn wl(band: &'static str, spectral: SpectralBand, min_nm: f64, max_nm: f64) -> Wavelength {
+    Wavelength {
+        band,
+        spectral,
+        min_nm,
+        max_nm,
+    }
+}
+
+const MODIS: &[Wavelength] = &[
+    wl("sur_refl_b03", SpectralBand::Blue, 459.0, 479.0),
+    wl("sur_refl_b04", SpectralBand::Green, 545.0, 565.0),
+    wl("sur_refl_b01", SpectralBand::Red, 620.0, 670.0),
+    wl("sur_refl_b02", SpectralBand::Nir, 841.0, 876.0),
+    wl("sur_refl_b06", SpectralBand::Swir1, 1628.0, 1652.0),
+    wl("sur_refl_b07", SpectralBand::Swir2, 2105.0, 2155.0),
+];
+
+// VNP09A1, 1 km M bands
+const VIIRS_M: &[Wavelength] = &[
+    wl("SurfReflect_M3", SpectralBand::Blue, 478.0, 498.0),
+    wl("SurfReflect_M4", SpectralBand::Green, 545.0, 565.0),
+    wl("SurfReflect_M5", SpectralBand::Red, 662.0, 682.0),
+    wl("SurfReflect_M7", SpectralBand::Nir, 846.0, 885.0),
+    wl("SurfReflect_M10", SpectralBand::Swir1, 1580.0, 1640.0),
+    wl("SurfReflect_M11", SpectralBand::Swir2, 2225.0, 2275.0),
+];
+
+// VNP09H1, 500 m I bands, no blue, green or SWIR2
+const VIIRS_I: &[Wavelength] = &[
+    wl("SurfReflect_I1", SpectralBand::Red, 600.0, 680.0),
+    wl("SurfReflect_I2", SpectralBand::Nir, 846.0, 885.0),
+    wl("SurfReflect_I3", SpectralBand::Swir1, 1580.0, 1640.0),
+];
+
+/// Band to wavelength mapping for a surface reflectance product
+pub fn wavelengths(product: ProductType) -> Option<&'static [Wavelength]> {
+    match product {
+        ProductType::MOD09A1 | ProductType::MYD09A1 => Some(MODIS),
+        ProductType::VNP09A1 => Some(VIIRS_M),
+        ProductType::VNP09H1 => Some(VIIRS_I),
+        _ => None,
+    }
+}
+
+/// Product band name for a spectral band
+pub fn band_name(product: ProductType, spectral: SpectralBand) -> Option<&'static str> {
+    wavelengths(product)?
+        .iter()
+        .find(|w| w.spectral == spectral)
+        .map(|w| w.band)
+}
+
+// Cloud free test on the state QA band
+fn modis_clear(state: i32) -> bool {
+    // bits 0-1 cloud state 00 clear, bit 2 cloud shadow, bit 10 internal cloud
+    state & 0b11 == 0 && state & (1 << 2) == 0 && state & (1 << 10) == 0
+}
+
+fn viirs_clear(state: i32) -> bool {
+    // bits 2-3 cloud confidence, 00 confident clear or 01 probably clear
+    (state >> 2) & 0b11 <= 1
+}
+
+/// Clear sky test on a state QA value
+pub type ClearSky = fn(i32) -> bool;
+
+/// State QA band and its clear sky test
+pub fn qc_band(product: ProductType) -> Option<(&'static str, ClearSky)> {
+    match product {
+        ProductType::MOD09A1 | ProductType::MYD09A1 => {
+            Some(("sur_refl_state_500m", modis_clear as ClearSky))
+        }
+        ProductType::VNP09A1 => Some(("SurfReflect_State", viirs_clear)),
+        ProductType::VNP09H1 => Some(("SurfReflect_State_500m", viirs_clear)),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Index {
+    /// (nir - red) / (nir + red)
+    Ndvi,
+    /// 2.5 (nir - red) / (nir + 6 red - 7.5 blue + 1)
+    Evi,
+    /// Normalized Burn Ratio, (nir - swir2) / (nir + swir2)
+    Nbr,
+    /// McFeeters water index, (green - nir) / (green + nir)
+    Ndwi,
+    /// Normalized Difference Moisture Index, (nir - swir1) / (nir + swir1)
+    Ndmi,
+    /// Soil Adjusted Vegetation Index with L = 0.5
+    Savi,
+    /// Near infrared reflectance of vegetation, NDVI x nir
+    Nirv,
+}
+
+impl Index {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Index::Ndvi => "NDVI",
+            Index::Evi => "EVI",
+            Index::Nbr => "NBR",
+            Index::Ndwi => "NDWI",
+            Index::Ndmi => "NDMI",
+            Index::Savi => "SAVI",
+            Index::Nirv => "NIRv",
+        }
+    }
+
+    pub fn required(&self) -> &'static [SpectralBand] {
+        use SpectralBand::*;
+        match self {
+            Index::Ndvi | Index::Savi | Index::Nirv => &[Red, Nir],
+            Index::Evi => &[Blue, Red, Nir],
+            Index::Nbr => &[Nir, Swir2],
+            Index::Ndwi => &[Green, Nir],
+            Index::Ndmi => &[Nir, Swir1],
+        }
+    }
+
+    /// Index from scaled reflectances (0-1), None if a band is missing or the result isn't finite
+    pub fn compute(&self, reflectance: impl Fn(SpectralBand) -> Option<f64>) -> Option<f64> {
+        let nd = |a: f64, b: f64| (a - b) / (a + b);
+        let r = |band| reflectance(band);
+        let value = match self {
+            Index::Ndvi => nd(r(SpectralBand::Nir)?, r(SpectralBand::Red)?),
+            Index::Evi => {
+                let (blue, red, nir) = (
+                    r(SpectralBand::Blue)?,
+                    r(SpectralBand::Red)?,
+                    r(SpectralBand::Nir)?,
+                );
+                2.5 * (nir - red) / (nir + 6.0 * red - 7.5 * blue + 1.0)
+            }
+            Index::Nbr => nd(r(SpectralBand::Nir)?, r(SpectralBand::Swir2)?),
+            Index::Ndwi => nd(r(SpectralBand::Green)?, r(SpectralBand::Nir)?),
+            Index::Ndmi => nd(r(SpectralBand::Nir)?, r(SpectralBand::Swir1)?),
+            Index::Savi => {
+                let (red, nir) = (r(SpectralBand::Red)?, r(SpectralBand::Nir)?);
+                1.5 * (nir - red) / (nir + red + 0.5)
+            }
+            Index::Nirv => {
+                let (red, nir) = (r(SpectralBand::Red)?, r(SpectralBand::Nir)?);
+                nd(nir, red) * nir
+            }
+        };
+        value.is_finite().then_some(value)
+    }
+}
+
+/// Index grid from reflectance grids on the same grid, masked with each band's fill/valid
+/// range and the optional QC filter first
+pub fn compute_index(
+    index: Index,
+    bands: &HashMap<SpectralBand, ModisData>,
+    qc: Option<&QcFilter<'_>>,
+) -> Result<ModisData, Box<dyn std::error::Error>> {
+    let required = index.required();
+    let first = bands
+        .get(&required[0])
+        .ok_or_else(|| format!("{} needs {:?}", index.name(), required[0]))?;
+    for spectral in required {
+        let data = bands
+            .get(spectral)
+            .ok_or_else(|| format!("{} needs {:?}", index.name(), spectral))?;
+        if !data.same_grid(first) {
+            return Err(format!("{:?} is on a different grid", spectral).into());
+        }
+    }
+    let masks: HashMap<SpectralBand, _> = required.iter().map(|s| (*s, bands[s].mask())).collect();
+
+    let subset = first
+        .subset
+        .iter()
+        .map(|s| {
+            // Matching date in each band
+            let dated: HashMap<SpectralBand, &Subset> = required
+                .iter()
+                .filter_map(|b| {
+                    bands[b]
+                        .subset
+                        .iter()
+                        .find(|o| o.modis_date == s.modis_date)
+                        .map(|o| (*b, o))
+                })
+                .collect();
+            let data = (0..s.data.len())
+                .map(|i| {
+                    if let Some(qc) = qc {
+                        if !qc.passes(&s.modis_date, i) {
+                            return INDEX_FILL;
+                        }
+                    }
+                    index
+                        .compute(|b| {
+                            let raw = *dated.get(&b)?.data.get(i)?;
+                            bands[&b].masked_value(raw, &masks[&b])
+                        })
+                        .map(|v| (v * INDEX_SCALE).round() as i32)
+                        .unwrap_or(INDEX_FILL)
+                })
+                .collect();
+            Subset {
+                band: index.name().to_string(),
+                data,
+                ..s.clone()
+            }
+        })
+        .collect();
+
+    Ok(ModisData {
+        band: index.name().to_string(),
+        units: String::new(),
+        scale: (1.0 / INDEX_SCALE).to_string(),
+        subset,
+        ..first.clone()
+    })
+}
+
+/// Fetch the bands (and with `qc` the state QA band) needed for `indices` through
+/// [`crate::subset`] and compute each index, in the order given
+#[allow(clippy::too_many_arguments)]
+pub async fn fetch_indices(
+    product: ProductType,
+    indices: &[Index],
+    latitude: f64,
+    longitude: f64,
+    start_date: &str,
+    end_date: &str,
+    km_above_below: u8,
+    km_left_right: u8,
+    qc: bool,
+) -> Result<Vec<ModisData>, Box<dyn std::error::Error>> {
+    let product_name: &str = product.into();
+    let mut spectral: Vec<SpectralBand> = indices
+        .iter()
+        .flat_map(|i| i.required().iter().copied())
+        .collect();
+    spectral.sort();
+    spectral.dedup();
+
+    let mut bands = HashMap::new();
+    for s in spectral {
+        let name =
+            band_name(product, s).ok_or_else(|| format!("{} has no {:?} band", product_name, s))?;
+        let data = crate::subset(
+            product_name,
+            latitude,
+            longitude,
+            name,
+            start_date,
+            end_date,
+            km_above_below,
+            km_left_right,
+        )
+        .await?;
+        bands.insert(s, data);
+    }
+
+    let state = if qc {
+        let (name, _) = qc_band(product).ok_or("no QC band for this product")?;
+        Some(
+            crate::subset(
+                product_name,
+                latitude,
+                longitude,
+                name,
+                start_date,
+                end_date,
+                km_above_below,
+                km_left_right,
+            )
+            .await?,
+        )
+    } else {
+        None
+    };
+    let filter = match (&state, qc_band(product)) {
+        (Some(state), Some((_, clear))) => Some(QcFilter::new(state, clear)),
+        _ => None,
+    };
+
+    indices
+        .iter()
+        .map(|index| compute_index(*index, &bands, filter.as_ref()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::otago;
+
+    fn band(name: &str, value: i32) -> ModisData {
+        let mut data = otago();
+        data.band = name.to_string();
+        data.scale = "0.0001".to_string();
+        data.subset[0].band = name.to_string();
+        data.subset[0].data = vec![value; 25];
+        data.subset[0].data[0] = -28672;
+        data
+    }
+
+    #[test]
+    fn test_compute() {
+        let r = |b| match b {
+            SpectralBand::Blue => Some(0.04),
+            SpectralBand::Red => Some(0.05),
+            SpectralBand::Nir => Some(0.35),
+            _ => None,
+        };
+        assert!((Index::Ndvi.compute(r).unwrap() - 0.75).abs() < 1e-9);
+        assert!((Index::Nirv.compute(r).unwrap() - 0.2625).abs() < 1e-9);
+        assert!((Index::Evi.compute(r).unwrap() - 0.75 / 1.35).abs() < 1e-9);
+        assert_eq!(Index::Nbr.compute(r), None);
+        assert_eq!(
+            band_name(ProductType::VNP09A1, SpectralBand::Nir),
+            Some("SurfReflect_M7")
+        );
+        assert_eq!(band_name(ProductType::VNP09H1, SpectralBand::Blue), None);
+    }
+
+    #[test]
+    fn test_compute_index() {
+        let bands = HashMap::from([
+            (SpectralBand::Red, band("sur_refl_b01", 500)),
+            (SpectralBand::Nir, band("sur_refl_b02", 3500)),
+        ]);
+        let ndvi = compute_index(Index::Ndvi, &bands, None).unwrap();
+        assert_eq!(ndvi.band, "NDVI");
+        assert_eq!(ndvi.subset[0].data[1], 7500);
+        assert_eq!(ndvi.subset[0].data[0], INDEX_FILL);
+        assert_eq!(ndvi.masked_value(7500, &ndvi.mask()), Some(0.75));
+
+        // Cloudy state QA on pixel 2
+        let mut state = otago();
+        state.subset[0].data = vec![0; 25];
+        state.subset[0].data[2] = 1;
+        let (_, clear) = qc_band(ProductType::MOD09A1).unwrap();
+        let filter = QcFilter::new(&state, clear);
+        let ndvi = compute_index(Index::Ndvi, &bands, Some(&filter)).unwrap();
+        assert_eq!(ndvi.subset[0].data[1], 7500);
+        assert_eq!(ndvi.subset[0].data[2], INDEX_FILL);
+
+        assert!(compute_index(Index::Nbr, &bands, None).is_err());
+    }
+}
@@ -0,0 +1,575 @@
+// MCD12Q1 land cover classes for every classification layer
+//
+// Class codes and names follow the MCD12Q1 collection 6.1 user guide. The user guide has no
+// colour tables, so colours are not official: the IGBP classes use the common LC_Type1
+// palette (as in the Earth Engine catalog), the same cover in other schemes reuses its IGBP
+// colour and covers only found in the LCCS layers have colours picked to sit among them.
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::dates::ModisDate;
+use crate::render::{Colormap, Rgba};
+use crate::stats::Window;
+use crate::structs::{ModisData, Subset};
+
+/// Code for unclassified pixels in every layer
+pub const UNCLASSIFIED: i32 = 255;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum Scheme {
+    /// LC_Type1, International Geosphere-Biosphere Programme
+    Igbp,
+    /// LC_Type2, University of Maryland
+    Umd,
+    /// LC_Type3, LAI/FPAR biomes
+    Lai,
+    /// LC_Type4, BIOME-BGC
+    Bgc,
+    /// LC_Type5, plant functional types
+    Pft,
+    /// LC_Prop1, FAO-LCCS land cover
+    LccsLandCover,
+    /// LC_Prop2, FAO-LCCS land use
+    LccsLandUse,
+    /// LC_Prop3, FAO-LCCS surface hydrology
+    LccsHydrology,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub struct LandCoverClass {
+    pub scheme: Scheme,
+    pub code: i32,
+    pub name: &'static str,
+    #[serde(skip)]
+    pub color: Rgba,
+}
+
+// Shared colours
+const WATER: u32 = 0x1c0dff;
+const ENF: u32 = 0x05450a;
+const EBF: u32 = 0x086a10;
+const DNF: u32 = 0x54a708;
+const DBF: u32 = 0x78d203;
+const MIXED: u32 = 0x009900;
+const CLOSED_SHRUB: u32 = 0xc6b044;
+const OPEN_SHRUB: u32 = 0xdcd159;
+const WOODY_SAVANNA: u32 = 0xdade48;
+const SAVANNA: u32 = 0xfbff13;
+const GRASS: u32 = 0xb6ff05;
+const WETLAND: u32 = 0x27ff87;
+const CROP: u32 = 0xc24f44;
+const URBAN: u32 = 0xa5a5a5;
+const MOSAIC: u32 = 0xff6d4c;
+const SNOW: u32 = 0x69fff8;
+const BARREN: u32 = 0xf9ffa4;
+
+// A class enum for a scheme, `Variant = code, name, colour;`
+macro_rules! land_cover_classes {
+    ($(#[$meta:meta])* $name:ident in $scheme:ident { $($variant:ident = $code:literal, $label:literal, $rgb:expr;)* }) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+        pub enum $name {
+            $($variant = $code,)*
+        }
+
+        impl $name {
+            /// Every class in code order
+            pub const ALL: &'static [$name] = &[$($name::$variant,)*];
+
+            pub fn code(self) -> i32 {
+                self as i32
+            }
+
+            pub fn name(self) -> &'static str {
+                match self {
+                    $($name::$variant => $label,)*
+                }
+            }
+
+            pub fn color(self) -> Rgba {
+                let rgb: u32 = match self {
+                    $($name::$variant => $rgb,)*
+                };
+                [(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8, 255]
+            }
+        }
+
+        impl TryFrom<i32> for $name {
+            type Error = String;
+
+            fn try_from(code: i32) -> Result<Self, Self::Error> {
+                match code {
+                    $($code => Ok($name::$variant),)*
+                    _ => Err(format!("no {} class {}", stringify!($name), code)),
+                }
+            }
+        }
+
+        impl From<$name> for LandCoverClass {
+            fn from(class: $name) -> LandCoverClass {
+                LandCoverClass {
+                    scheme: Scheme::$scheme,
+                    code: class.code(),
+                    name: class.name(),
+                    color: class.color(),
+                }
+            }
+        }
+    };
+}
+
+land_cover_classes! {
+    /// LC_Type1 (IGBP) classes
+    IgbpClass in Igbp {
+        EvergreenNeedleleafForests = 1, "Evergreen Needleleaf Forests", ENF;
+        EvergreenBroadleafForests = 2, "Evergreen Broadleaf Forests", EBF;
+        DeciduousNeedleleafForests = 3, "Deciduous Needleleaf Forests", DNF;
+        DeciduousBroadleafForests = 4, "Deciduous Broadleaf Forests", DBF;
+        MixedForests = 5, "Mixed Forests", MIXED;
+        ClosedShrublands = 6, "Closed Shrublands", CLOSED_SHRUB;
+        OpenShrublands = 7, "Open Shrublands", OPEN_SHRUB;
+        WoodySavannas = 8, "Woody Savannas", WOODY_SAVANNA;
+        Savannas = 9, "Savannas", SAVANNA;
+        Grasslands = 10, "Grasslands", GRASS;
+        PermanentWetlands = 11, "Permanent Wetlands", WETLAND;
+        Croplands = 12, "Croplands", CROP;
+        UrbanAndBuiltUpLands = 13, "Urban and Built-up Lands", URBAN;
+        CroplandNaturalVegetationMosaics = 14, "Cropland/Natural Vegetation Mosaics", MOSAIC;
+        PermanentSnowAndIce = 15, "Permanent Snow and Ice", SNOW;
+        Barren = 16, "Barren", BARREN;
+        WaterBodies = 17, "Water Bodies", WATER;
+    }
+}
+
+land_cover_classes! {
+    /// LC_Type2 (UMD) classes
+    UmdClass in Umd {
+        WaterBodies = 0, "Water Bodies", WATER;
+        EvergreenNeedleleafForests = 1, "Evergreen Needleleaf Forests", ENF;
+        EvergreenBroadleafForests = 2, "Evergreen Broadleaf Forests", EBF;
+        DeciduousNeedleleafForests = 3, "Deciduous Needleleaf Forests", DNF;
+        DeciduousBroadleafForests = 4, "Deciduous Broadleaf Forests", DBF;
+        MixedForests = 5, "Mixed Forests", MIXED;
+        ClosedShrublands = 6, "Closed Shrublands", CLOSED_SHRUB;
+        OpenShrublands = 7, "Open Shrublands", OPEN_SHRUB;
+        WoodySavannas = 8, "Woody Savannas", WOODY_SAVANNA;
+        Savannas = 9, "Savannas", SAVANNA;
+        Grasslands = 10, "Grasslands", GRASS;
+        PermanentWetlands = 11, "Permanent Wetlands", WETLAND;
+        Croplands = 12, "Croplands", CROP;
+        UrbanAndBuiltUpLands = 13, "Urban and Built-up Lands", URBAN;
+        CroplandNaturalVegetationMosaics = 14, "Cropland/Natural Vegetation Mosaics", MOSAIC;
+        NonVegetatedLands = 15, "Non-Vegetated Lands", BARREN;
+    }
+}
+
+land_cover_classes! {
+    /// LC_Type3 (LAI/FPAR biome) classes
+    LaiClass in Lai {
+        WaterBodies = 0, "Water Bodies", WATER;
+        Grasslands = 1, "Grasslands", GRASS;
+        Shrublands = 2, "Shrublands", CLOSED_SHRUB;
+        BroadleafCroplands = 3, "Broadleaf Croplands", CROP;
+        Savannas = 4, "Savannas", SAVANNA;
+        EvergreenBroadleafForests = 5, "Evergreen Broadleaf Forests", EBF;
+        DeciduousBroadleafForests = 6, "Deciduous Broadleaf Forests", DBF;
+        EvergreenNeedleleafForests = 7, "Evergreen Needleleaf Forests", ENF;
+        DeciduousNeedleleafForests = 8, "Deciduous Needleleaf Forests", DNF;
+        NonVegetatedLands = 9, "Non-Vegetated Lands", BARREN;
+        UrbanAndBuiltUpLands = 10, "Urban and Built-up Lands", URBAN;
+    }
+}
+
+land_cover_classes! {
+    /// LC_Type4 (BIOME-BGC) classes
+    BgcClass in Bgc {
+        WaterBodies = 0, "Water Bodies", WATER;
+        EvergreenNeedleleafVegetation = 1, "Evergreen Needleleaf Vegetation", ENF;
+        EvergreenBroadleafVegetation = 2, "Evergreen Broadleaf Vegetation", EBF;
+        DeciduousNeedleleafVegetation = 3, "Deciduous Needleleaf Vegetation", DNF;
+        DeciduousBroadleafVegetation = 4, "Deciduous Broadleaf Vegetation", DBF;
+        AnnualBroadleafVegetation = 5, "Annual Broadleaf Vegetation", CROP;
+        AnnualGrassVegetation = 6, "Annual Grass Vegetation", GRASS;
+        NonVegetatedLands = 7, "Non-Vegetated Lands", BARREN;
+        UrbanAndBuiltUpLands = 8, "Urban and Built-up Lands", URBAN;
+    }
+}
+
+land_cover_classes! {
+    /// LC_Type5 (plant functional type) classes
+    PftClass in Pft {
+        WaterBodies = 0, "Water Bodies", WATER;
+        EvergreenNeedleleafTrees = 1, "Evergreen Needleleaf Trees", ENF;
+        EvergreenBroadleafTrees = 2, "Evergreen Broadleaf Trees", EBF;
+        DeciduousNeedleleafTrees = 3, "Deciduous Needleleaf Trees", DNF;
+        DeciduousBroadleafTrees = 4, "Deciduous Broadleaf Trees", DBF;
+        Shrub = 5, "Shrub", CLOSED_SHRUB;
+        Grass = 6, "Grass", GRASS;
+        CerealCroplands = 7, "Cereal Croplands", MOSAIC;
+        BroadleafCroplands = 8, "Broadleaf Croplands", CROP;
+        UrbanAndBuiltUpLands = 9, "Urban and Built-up Lands", URBAN;
+        PermanentSnowAndIce = 10, "Permanent Snow and Ice", SNOW;
+        Barren = 11, "Barren", BARREN;
+    }
+}
+
+land_cover_classes! {
+    /// LC_Prop1 (FAO-LCCS land cover) classes
+    LccsLandCoverClass in LccsLandCover {
+        Barren = 1, "Barren", BARREN;
+        PermanentSnowAndIce = 2, "Permanent Snow and Ice", SNOW;
+        WaterBodies = 3, "Water Bodies", WATER;
+        EvergreenNeedleleafForests = 11, "Evergreen Needleleaf Forests", ENF;
+        EvergreenBroadleafForests = 12, "Evergreen Broadleaf Forests", EBF;
+        DeciduousNeedleleafForests = 13, "Deciduous Needleleaf Forests", DNF;
+        DeciduousBroadleafForests = 14, "Deciduous Broadleaf Forests", DBF;
+        MixedBroadleafNeedleleafForests = 15, "Mixed Broadleaf/Needleleaf Forests", MIXED;
+        MixedBroadleafEvergreenDeciduousForests = 16, "Mixed Broadleaf Evergreen/Deciduous Forests", 0x00c000;
+        OpenForests = 21, "Open Forests", WOODY_SAVANNA;
+        SparseForests = 22, "Sparse Forests", SAVANNA;
+        DenseHerbaceous = 31, "Dense Herbaceous", GRASS;
+        SparseHerbaceous = 32, "Sparse Herbaceous", 0xdfff80;
+        DenseShrublands = 41, "Dense Shrublands", CLOSED_SHRUB;
+        ShrublandGrasslandMosaics = 42, "Shrubland/Grassland Mosaics", 0xd2c85a;
+        SparseShrublands = 43, "Sparse Shrublands", OPEN_SHRUB;
+    }
+}
+
+land_cover_classes! {
+    /// LC_Prop2 (FAO-LCCS land use) classes
+    LccsLandUseClass in LccsLandUse {
+        Barren = 1, "Barren", BARREN;
+        PermanentSnowAndIce = 2, "Permanent Snow and Ice", SNOW;
+        WaterBodies = 3, "Water Bodies", WATER;
+        UrbanAndBuiltUpLands = 9, "Urban and Built-up Lands", URBAN;
+        DenseForests = 10, "Dense Forests", EBF;
+        OpenForests = 20, "Open Forests", WOODY_SAVANNA;
+        ForestCroplandMosaics = 25, "Forest/Cropland Mosaics", 0xa0a040;
+        NaturalHerbaceous = 30, "Natural Herbaceous", GRASS;
+        NaturalHerbaceousCroplandsMosaics = 35, "Natural Herbaceous/Croplands Mosaics", MOSAIC;
+        HerbaceousCroplands = 36, "Herbaceous Croplands", CROP;
+        Shrublands = 40, "Shrublands", CLOSED_SHRUB;
+    }
+}
+
+land_cover_classes! {
+    /// LC_Prop3 (FAO-LCCS surface hydrology) classes
+    LccsHydrologyClass in LccsHydrology {
+        Barren = 1, "Barren", BARREN;
+        PermanentSnowAndIce = 2, "Permanent Snow and Ice", SNOW;
+        WaterBodies = 3, "Water Bodies", WATER;
+        DenseForests = 10, "Dense Forests", EBF;
+        OpenForests = 20, "Open Forests", WOODY_SAVANNA;
+        WoodyWetlands = 27, "Woody Wetlands", 0x00a070;
+        Grasslands = 30, "Grasslands", GRASS;
+        Shrublands = 40, "Shrublands", CLOSED_SHRUB;
+        HerbaceousWetlands = 50, "Herbaceous Wetlands", WETLAND;
+        Tundra = 51, "Tundra", 0xb4d2c8;
+    }
+}
+
+impl Scheme {
+    pub const ALL: [Scheme; 8] = [
+        Scheme::Igbp,
+        Scheme::Umd,
+        Scheme::Lai,
+        Scheme::Bgc,
+        Scheme::Pft,
+        Scheme::LccsLandCover,
+        Scheme::LccsLandUse,
+        Scheme::LccsHydrology,
+    ];
+
+    /// MCD12Q1 band name
+    pub fn band(&self) -> &'static str {
+        match self {
+            Scheme::Igbp => "LC_Type1",
+            Scheme::Umd => "LC_Type2",
+            Scheme::Lai => "LC_Type3",
+            Scheme::Bgc => "LC_Type4",
+            Scheme::Pft => "LC_Type5",
+            Scheme::LccsLandCover => "LC_Prop1",
+            Scheme::LccsLandUse => "LC_Prop2",
+            Scheme::LccsHydrology => "LC_Prop3",
+        }
+    }
+
+    pub fn from_band(band: &str) -> Option<Scheme> {
+        Scheme::ALL.into_iter().find(|s| s.band() == band)
+    }
+
+    /// "igbp", "umd", "lai", "bgc", "pft", "lccs1", "lccs2" or "lccs3"
+    pub fn from_name(name: &str) -> Option<Scheme> {
+        match name.to_lowercase().as_str() {
+            "igbp" => Some(Scheme::Igbp),
+            "umd" => Some(Scheme::Umd),
+            "lai" => Some(Scheme::Lai),
+            "bgc" => Some(Scheme::Bgc),
+            "pft" => Some(Scheme::Pft),
+            "lccs1" => Some(Scheme::LccsLandCover),
+            "lccs2" => Some(Scheme::LccsLandUse),
+            "lccs3" => Some(Scheme::LccsHydrology),
+            _ => None,
+        }
+    }
+
+    /// Every class in code order, not including unclassified
+    pub fn classes(&self) -> Vec<LandCoverClass> {
+        match self {
+            Scheme::Igbp => IgbpClass::ALL.iter().map(|c| (*c).into()).collect(),
+            Scheme::Umd => UmdClass::ALL.iter().map(|c| (*c).into()).collect(),
+            Scheme::Lai => LaiClass::ALL.iter().map(|c| (*c).into()).collect(),
+            Scheme::Bgc => BgcClass::ALL.iter().map(|c| (*c).into()).collect(),
+            Scheme::Pft => PftClass::ALL.iter().map(|c| (*c).into()).collect(),
+            Scheme::LccsLandCover => LccsLandCoverClass::ALL
+                .iter()
+                .map(|c| (*c).into())
+                .collect(),
+            Scheme::LccsLandUse => LccsLandUseClass::ALL.iter().map(|c| (*c).into()).collect(),
+            Scheme::LccsHydrology => LccsHydrologyClass::ALL
+                .iter()
+                .map(|c| (*c).into())
+                .collect(),
+        }
+    }
+
+    /// None for unclassified and unknown codes
+    pub fn class(&self, code: i32) -> Option<LandCoverClass> {
+        self.classes().into_iter().find(|c| c.code == code)
+    }
+
+    pub fn colormap(&self) -> Colormap {
+        Colormap::Categorical(self.classes().iter().map(|c| (c.code, c.color)).collect())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ClassHistogram {
+    pub date: ModisDate,
+    /// Pixels per class code, unclassified pixels are not counted
+    pub counts: BTreeMap<i32, usize>,
+    /// Pixels in the window, classified or not
+    pub pixels: usize,
+}
+
+impl ClassHistogram {
+    pub fn classified(&self) -> usize {
+        self.counts.values().sum()
+    }
+
+    /// Share of the classified pixels in `code`
+    pub fn fraction(&self, code: i32) -> f64 {
+        let classified = self.classified();
+        if classified == 0 {
+            return 0.0;
+        }
+        self.counts.get(&code).copied().unwrap_or(0) as f64 / classified as f64
+    }
+
+    /// Most common class, the lowest code on ties
+    pub fn majority(&self) -> Option<i32> {
+        self.counts
+            .iter()
+            .max_by(|a, b| a.1.cmp(b.1).then(b.0.cmp(a.0)))
+            .map(|(code, _)| *code)
+    }
+}
+
+/// Pixel counts of class transitions between two dates, `counts[(from, to)]`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChangeMatrix {
+    pub from: ModisDate,
+    pub to: ModisDate,
+    pub counts: BTreeMap<(i32, i32), usize>,
+}
+
+impl ChangeMatrix {
+    pub fn unchanged(&self) -> usize {
+        self.counts
+            .iter()
+            .filter(|((a, b), _)| a == b)
+            .map(|(_, n)| n)
+            .sum()
+    }
+
+    pub fn changed(&self) -> usize {
+        self.counts
+            .iter()
+            .filter(|((a, b), _)| a != b)
+            .map(|(_, n)| n)
+            .sum()
+    }
+
+    /// Class codes appearing on either side, for laying the matrix out as a table
+    pub fn classes(&self) -> Vec<i32> {
+        let mut classes: Vec<i32> = self.counts.keys().flat_map(|(a, b)| [*a, *b]).collect();
+        classes.sort();
+        classes.dedup();
+        classes
+    }
+}
+
+impl ModisData {
+    /// Classification scheme of a MCD12Q1 layer
+    pub fn land_cover_scheme(&self) -> Option<Scheme> {
+        Scheme::from_band(&self.band)
+    }
+
+    /// Class of every pixel in the first subset of `year` (row-major), None when unclassified
+    pub fn land_cover(&self, year: i32) -> Option<Vec<Option<LandCoverClass>>> {
+        let scheme = self.land_cover_scheme()?;
+        let subset = self.land_cover_subset(year)?;
+        Some(subset.data.iter().map(|v| scheme.class(*v)).collect())
+    }
+
+    /// Typed class of every pixel in the first subset of `year`, e.g.
+    /// `land_cover_as::<IgbpClass>(2019)`, None for codes outside `C` such as unclassified
+    pub fn land_cover_as<C: TryFrom<i32>>(&self, year: i32) -> Option<Vec<Option<C>>> {
+        let subset = self.land_cover_subset(year)?;
+        Some(subset.data.iter().map(|v| C::try_from(*v).ok()).collect())
+    }
+
+    fn land_cover_subset(&self, year: i32) -> Option<&Subset> {
+        self.subset
+            .iter()
+            .find(|s| s.date().map(|d| d.year()) == Some(year))
+    }
+
+    /// Class histogram of the window for each date, in date order
+    pub fn class_histograms(&self, window: Window) -> Vec<ClassHistogram> {
+        let scheme = self.land_cover_scheme();
+        let indices = self.window_indices(window);
+        let mut histograms: Vec<ClassHistogram> = self
+            .subset
+            .iter()
+            .filter_map(|subset| {
+                let mut counts = BTreeMap::new();
+                for v in indices.iter().filter_map(|i| subset.data.get(*i)) {
+                    let known = match scheme {
+                        Some(scheme) => scheme.class(*v).is_some(),
+                        None => *v != UNCLASSIFIED,
+                    };
+                    if known {
+                        *counts.entry(*v).or_insert(0) += 1;
+                    }
+                }
+                Some(ClassHistogram {
+                    date: subset.date()?,
+                    counts,
+                    pixels: indices.len(),
+                })
+            })
+            .collect();
+        histograms.sort_by_key(|h| h.date);
+        histograms
+    }
+
+    /// Transitions between the layers of two years, pixels unclassified in either are left out
+    pub fn change_matrix(
+        &self,
+        from_year: i32,
+        to_year: i32,
+    ) -> Result<ChangeMatrix, Box<dyn std::error::Error>> {
+        let from = self
+            .land_cover_subset(from_year)
+            .ok_or_else(|| format!("no land cover for {}", from_year))?;
+        let to = self
+            .land_cover_subset(to_year)
+            .ok_or_else(|| format!("no land cover for {}", to_year))?;
+        let mut counts = BTreeMap::new();
+        for (a, b) in from.data.iter().zip(&to.data) {
+            if *a == UNCLASSIFIED || *b == UNCLASSIFIED {
+                continue;
+            }
+            *counts.entry((*a, *b)).or_insert(0) += 1;
+        }
+        Ok(ChangeMatrix {
+            from: from.date().ok_or("bad date")?,
+            to: to.date().ok_or("bad date")?,
+            counts,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::otago;
+
+    fn landcover() -> ModisData {
+        let mut data = otago();
+        data.band = "LC_Type1".to_string();
+        data.subset[0].modis_date = "A2019001".to_string();
+        data.subset[0].calendar_date = "2019-01-01".to_string();
+        data.subset[0].data = vec![10; 25];
+        data.subset[0].data[0] = 12;
+        data.subset[0].data[1] = UNCLASSIFIED;
+        let mut later = data.subset[0].clone();
+        later.modis_date = "A2020001".to_string();
+        later.calendar_date = "2020-01-01".to_string();
+        later.data[2] = 13;
+        later.data[3] = 13;
+        data.subset.push(later);
+        data
+    }
+
+    #[test]
+    fn test_classes() {
+        assert_eq!(Scheme::from_band("LC_Type3"), Some(Scheme::Lai));
+        let grass = Scheme::Igbp.class(10).unwrap();
+        assert_eq!(grass.name, "Grasslands");
+        assert_eq!(grass.color, [0xb6, 0xff, 0x05, 255]);
+        assert_eq!(Scheme::Umd.class(0).unwrap().name, "Water Bodies");
+        assert_eq!(Scheme::LccsHydrology.class(51).unwrap().name, "Tundra");
+        assert_eq!(Scheme::Igbp.class(UNCLASSIFIED), None);
+        for scheme in Scheme::ALL {
+            let classes = scheme.classes();
+            assert!(classes.windows(2).all(|w| w[0].code < w[1].code));
+        }
+
+        assert_eq!(IgbpClass::try_from(10), Ok(IgbpClass::Grasslands));
+        assert_eq!(IgbpClass::Grasslands.code(), 10);
+        assert!(IgbpClass::try_from(UNCLASSIFIED).is_err());
+        assert!(IgbpClass::try_from(0).is_err());
+        assert_eq!(UmdClass::try_from(0), Ok(UmdClass::WaterBodies));
+        assert_eq!(
+            LccsHydrologyClass::try_from(51).map(|c| c.name()),
+            Ok("Tundra")
+        );
+        assert_eq!(
+            LandCoverClass::from(PftClass::CerealCroplands),
+            Scheme::Pft.class(7).unwrap()
+        );
+        for class in LccsLandUseClass::ALL {
+            assert_eq!(LccsLandUseClass::try_from(class.code()), Ok(*class));
+        }
+    }
+
+    #[test]
+    fn test_histogram_and_change() {
+        let data = landcover();
+        let histograms = data.class_histograms(Window::All);
+        assert_eq!(histograms.len(), 2);
+        assert_eq!(histograms[0].classified(), 24);
+        assert_eq!(histograms[0].majority(), Some(10));
+        assert_eq!(histograms[1].counts[&13], 2);
+        assert!((histograms[0].fraction(12) - 1.0 / 24.0).abs() < 1e-9);
+
+        let classes = data.land_cover(2019).unwrap();
+        assert_eq!(classes[0].unwrap().name, "Croplands");
+        assert_eq!(classes[1], None);
+        let typed = data.land_cover_as::<IgbpClass>(2019).unwrap();
+        assert_eq!(typed[0], Some(IgbpClass::Croplands));
+        assert_eq!(typed[1], None);
+        assert_eq!(typed[2], Some(IgbpClass::Grasslands));
+
+        let change = data.change_matrix(2019, 2020).unwrap();
+        assert_eq!(change.counts[&(10, 13)], 2);
+        assert_eq!(change.changed(), 2);
+        assert_eq!(change.unchanged(), 22);
+        assert_eq!(change.classes(), vec![10, 12, 13]);
+        assert!(data.change_matrix(2019, 2021).is_err());
+    }
+}
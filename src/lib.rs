@@ -2,9 +2,45 @@
 
 const BASE_URL: &str = "https://modis.ornl.gov/rst/api/v1/";
 
+pub mod animate;
+#[cfg(feature = "arrow")]
+pub mod arrow;
+pub mod ascii;
+pub mod dates;
+pub mod export;
+pub mod fire;
+pub mod geojson;
+pub mod grid;
+pub mod indices;
+pub mod landcover;
+pub mod lst;
+pub mod mask;
+pub mod mosaic;
+pub mod netcdf;
+pub mod platform;
+pub mod polygon;
+pub mod region;
+pub mod render;
+pub mod reproject;
+pub mod sinusoidal;
+pub mod stats;
+#[cfg(feature = "sqlite")]
+pub mod store;
 pub mod structs;
-
+pub mod sync;
+pub mod timeseries;
+pub mod trend;
+pub mod utm;
+pub mod zonal;
+
+pub use ascii::*;
+pub use dates::*;
+pub use export::*;
+pub use mask::*;
+pub use netcdf::*;
+pub use stats::{Summary, Window, WindowOptions, WindowStats};
 pub use structs::*;
+pub use timeseries::TimeSeries;
 
 // https://modis.ornl.gov/rst/api/v1/products
 pub async fn products() -> Result<ProductsData, Box<dyn std::error::Error>> {
@@ -49,6 +85,21 @@ pub async fn dates(
     Ok(response)
 }
 
+// Bands
+// https://modis.ornl.gov/rst/api/v1/MOD13Q1/bands
+pub async fn bands(product: &str) -> Result<BandsData, Box<dyn std::error::Error>> {
+    // Python
+    // response = requests.get('https://modis.ornl.gov/rst/api/v1/MOD13Q1/bands', headers=header)
+    // bands = json.loads(response.text)['bands']
+
+    let response = reqwest::get(format!("{}/{}/bands", BASE_URL, product))
+        .await?
+        .json::<BandsData>()
+        .await?;
+
+    Ok(response)
+}
+
 // Sites
 // view-source:https://modis.ornl.gov/rst/api/v1/sites
 pub async fn sites() -> Result<Sites, Box<dyn std::error::Error>> {
@@ -70,6 +121,7 @@ pub async fn sites() -> Result<Sites, Box<dyn std::error::Error>> {
 // band=LST_Day_1km&//
 // startDate=A2001001&endDate=A2001001&
 // kmAboveBelow=1&kmLeftRight=1', headers=header)
+#[allow(clippy::too_many_arguments)]
 pub async fn subset(
     product: &str,
     latitude: f64,
@@ -98,6 +150,44 @@ pub async fn subset(
 mod tests {
     use super::*;
 
+    // Otago LAI subset from examples/lai.rs, used as a fixture by the other modules
+    pub fn otago() -> ModisData {
+        ModisData {
+            xllcorner: "13213215.26".to_string(),
+            yllcorner: "-5101536.36".to_string(),
+            cellsize: 463.312716528,
+            nrows: 5,
+            ncols: 5,
+            band: "Lai_500m".to_string(),
+            units: "m^2/m^2".to_string(),
+            scale: "0.1".to_string(),
+            latitude: -45.8667,
+            longitude: 170.6667,
+            header: "https://modisrest.ornl.gov/rst/api/v1/MCD15A2H/subset?latitude=-45.8667&longitude=170.6667&band=Lai_500m&startDate=A2024217&endDate=A2024217&kmAboveBelow=1&kmLeftRight=1".to_string(),
+            subset: vec![Subset {
+                modis_date: "A2024217".to_string(),
+                calendar_date: "2024-08-04".to_string(),
+                band: "Lai_500m".to_string(),
+                tile: "h29v13".to_string(),
+                proc_date: "2024228030354".to_string(),
+                data: vec![
+                    13, 13, 9, 9, 6, 10, 9, 4, 254, 254, 11, 1, 254, 3, 9, 5, 5, 5, 9, 11, 1, 1,
+                    18, 254, 254,
+                ],
+            }],
+        }
+    }
+
+    #[test]
+    fn test_grid() {
+        let data = otago();
+        assert_eq!(data.product(), Some("MCD15A2H"));
+        assert_eq!(data.scale_factor(), Some(0.1));
+        assert_eq!(data.pixel_at(data.latitude, data.longitude), Some((2, 2)));
+        let (lat, lon) = data.pixel_lat_lon(2, 2);
+        assert_eq!(data.pixel_at(lat, lon), Some((2, 2)));
+    }
+
     #[tokio::test]
     async fn test_dates() {
         dates(ProductType::MOD11A2.into(), 39.56499, -121.55527)
@@ -110,10 +200,18 @@ mod tests {
         products().await.expect("Failed to fetch products");
     }
 
+    #[tokio::test]
+    async fn test_bands() {
+        let a = bands(ProductType::MCD15A2H.into())
+            .await
+            .expect("Failed to fetch bands");
+        assert!(a.bands.iter().any(|b| b.band == "Lai_500m"));
+    }
+
     #[tokio::test]
     async fn test_sites() {
         let a = sites().await.expect("Failed to fetch sites");
-        assert!(a.sites.len() > 0);
+        assert!(!a.sites.is_empty());
     }
 
     #[tokio::test]
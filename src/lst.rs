@@ -0,0 +1,327 @@
+// Land surface temperature helpers for MOD11A2/MYD11A2 and MOD21A2/MYD21A2
+//
+// LST is stored as Kelvin x 50 (scale 0.02), 0 is fill. The two product families pack
+// their QC bits differently, see LstQc::decode.
+
+use serde::Serialize;
+
+use crate::dates::ModisDate;
+use crate::structs::{ModisData, ProductType};
+use crate::timeseries::TimeSeries;
+
+pub const KELVIN_OFFSET: f64 = 273.15;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TemperatureUnit {
+    Kelvin,
+    Celsius,
+}
+
+impl TemperatureUnit {
+    pub fn from_kelvin(&self, kelvin: f64) -> f64 {
+        match self {
+            TemperatureUnit::Kelvin => kelvin,
+            TemperatureUnit::Celsius => kelvin - KELVIN_OFFSET,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overpass {
+    Day,
+    Night,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QcLayout {
+    /// MOD11A2/MYD11A2, 8 bit
+    Mod11,
+    /// MOD21A2/MYD21A2, 16 bit
+    Mod21,
+}
+
+impl QcLayout {
+    pub fn for_product(product: ProductType) -> Option<QcLayout> {
+        match product {
+            ProductType::MOD11A2 | ProductType::MYD11A2 => Some(QcLayout::Mod11),
+            ProductType::MOD21A2 | ProductType::MYD21A2 => Some(QcLayout::Mod21),
+            _ => None,
+        }
+    }
+}
+
+/// Band names of the day/night LST and QC layers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LstBands {
+    pub day: &'static str,
+    pub night: &'static str,
+    pub qc_day: &'static str,
+    pub qc_night: &'static str,
+}
+
+impl LstBands {
+    pub fn for_product(product: ProductType) -> Option<LstBands> {
+        match QcLayout::for_product(product)? {
+            QcLayout::Mod11 => Some(LstBands {
+                day: "LST_Day_1km",
+                night: "LST_Night_1km",
+                qc_day: "QC_Day",
+                qc_night: "QC_Night",
+            }),
+            QcLayout::Mod21 => Some(LstBands {
+                day: "LST_Day_1KM",
+                night: "LST_Night_1KM",
+                qc_day: "QC_Day",
+                qc_night: "QC_Night",
+            }),
+        }
+    }
+}
+
+/// Decoded LST QC value
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct LstQc {
+    /// Mandatory QA bits, 0 good, 1 other quality, 2 not produced (cloud), 3 not produced
+    pub mandatory: u8,
+    /// Data quality bits, 0 good (their meaning for 1-3 differs between layouts)
+    pub data_quality: u8,
+    /// Upper bound of the emissivity error, infinite for the worst class
+    pub emissivity_error: f64,
+    /// Upper bound of the LST error in K, infinite for the worst class
+    pub lst_error: f64,
+}
+
+impl LstQc {
+    pub fn decode(layout: QcLayout, value: i32) -> LstQc {
+        let bits = |shift: u32| ((value >> shift) & 0b11) as u8;
+        match layout {
+            // bits 4-5 emissivity error <=0.01/0.02/0.04/more, 6-7 LST error <=1/2/3/more K
+            QcLayout::Mod11 => LstQc {
+                mandatory: bits(0),
+                data_quality: bits(2),
+                emissivity_error: [0.01, 0.02, 0.04, f64::INFINITY][bits(4) as usize],
+                lst_error: [1.0, 2.0, 3.0, f64::INFINITY][bits(6) as usize],
+            },
+            // bits 12-13 emissivity accuracy and 14-15 LST accuracy, 3 is the best class
+            QcLayout::Mod21 => LstQc {
+                mandatory: bits(0),
+                data_quality: bits(2),
+                emissivity_error: [f64::INFINITY, 0.02, 0.015, 0.01][bits(12) as usize],
+                lst_error: [f64::INFINITY, 2.0, 1.5, 1.0][bits(14) as usize],
+            },
+        }
+    }
+
+    pub fn is_produced(&self) -> bool {
+        self.mandatory <= 1
+    }
+
+    /// Produced, and within `max_lst_error` K when given
+    pub fn passes(&self, max_lst_error: Option<f64>) -> bool {
+        self.is_produced() && max_lst_error.map(|e| self.lst_error <= e).unwrap_or(true)
+    }
+}
+
+impl ModisData {
+    /// Scaled LST of a raw value, None for fill
+    pub fn temperature(&self, value: i32, unit: TemperatureUnit) -> Option<f64> {
+        self.masked_value(value, &self.mask())
+            .map(|k| unit.from_kelvin(k))
+    }
+}
+
+/// Day and night LST with their QC layers for one request
+#[derive(Debug, Clone)]
+pub struct Lst {
+    pub product: ProductType,
+    pub day: ModisData,
+    pub night: ModisData,
+    pub qc_day: ModisData,
+    pub qc_night: ModisData,
+}
+
+impl Lst {
+    /// Fetch the Day/Night LST and QC bands through [`crate::subset`]
+    #[allow(clippy::too_many_arguments)]
+    pub async fn fetch(
+        product: ProductType,
+        latitude: f64,
+        longitude: f64,
+        start_date: &str,
+        end_date: &str,
+        km_above_below: u8,
+        km_left_right: u8,
+    ) -> Result<Lst, Box<dyn std::error::Error>> {
+        let bands = LstBands::for_product(product).ok_or("not an LST product")?;
+        let name: &str = product.into();
+        let mut layers = Vec::new();
+        for band in [bands.day, bands.night, bands.qc_day, bands.qc_night] {
+            layers.push(
+                crate::subset(
+                    name,
+                    latitude,
+                    longitude,
+                    band,
+                    start_date,
+                    end_date,
+                    km_above_below,
+                    km_left_right,
+                )
+                .await?,
+            );
+        }
+        let mut layers = layers.into_iter();
+        Ok(Lst {
+            product,
+            day: layers.next().unwrap(),
+            night: layers.next().unwrap(),
+            qc_day: layers.next().unwrap(),
+            qc_night: layers.next().unwrap(),
+        })
+    }
+
+    /// LST series of a pixel, QC failures and fill are None, see [`LstQc::passes`]
+    pub fn series(
+        &self,
+        overpass: Overpass,
+        row: usize,
+        col: usize,
+        unit: TemperatureUnit,
+        max_lst_error: Option<f64>,
+    ) -> Option<TimeSeries> {
+        let layout = QcLayout::for_product(self.product)?;
+        let (lst, qc) = match overpass {
+            Overpass::Day => (&self.day, &self.qc_day),
+            Overpass::Night => (&self.night, &self.qc_night),
+        };
+        if row >= lst.nrows.max(0) as usize || col >= lst.ncols.max(0) as usize {
+            return None;
+        }
+        let i = row * lst.ncols as usize + col;
+        Some(
+            lst.subset
+                .iter()
+                .filter_map(|s| {
+                    let date = s.date()?;
+                    let passes = qc
+                        .subset
+                        .iter()
+                        .find(|q| q.modis_date == s.modis_date)
+                        .and_then(|q| q.data.get(i))
+                        .map(|v| LstQc::decode(layout, *v).passes(max_lst_error))
+                        .unwrap_or(false);
+                    let value = s
+                        .data
+                        .get(i)
+                        .filter(|_| passes)
+                        .and_then(|v| lst.temperature(*v, unit));
+                    Some((date, value))
+                })
+                .collect(),
+        )
+    }
+
+    /// Day minus night LST of a pixel (K, the same in °C), None unless both pass QC
+    pub fn day_night_difference(
+        &self,
+        row: usize,
+        col: usize,
+        max_lst_error: Option<f64>,
+    ) -> Option<TimeSeries> {
+        let unit = TemperatureUnit::Kelvin;
+        let day = self.series(Overpass::Day, row, col, unit, max_lst_error)?;
+        let night = self.series(Overpass::Night, row, col, unit, max_lst_error)?;
+        Some(
+            day.iter()
+                .map(|(date, d)| (*date, d.zip(night.get(*date)).map(|(d, n)| d - n)))
+                .collect(),
+        )
+    }
+}
+
+/// Mean of Terra and Aqua series on the same dates, with `require_both` false a date with
+/// only one valid overpass keeps that value
+pub fn terra_aqua_mean(terra: &TimeSeries, aqua: &TimeSeries, require_both: bool) -> TimeSeries {
+    let mut dates: Vec<ModisDate> = terra.dates().chain(aqua.dates()).collect();
+    dates.sort();
+    dates.dedup();
+    dates
+        .into_iter()
+        .map(|date| {
+            let value = match (terra.get(date), aqua.get(date)) {
+                (Some(t), Some(a)) => Some((t + a) / 2.0),
+                (Some(v), None) | (None, Some(v)) if !require_both => Some(v),
+                _ => None,
+            };
+            (date, value)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::otago;
+
+    fn lst(band: &str, values: Vec<i32>) -> ModisData {
+        let mut data = otago();
+        data.band = band.to_string();
+        data.scale = "0.02".to_string();
+        data.subset[0].data = values;
+        data
+    }
+
+    #[test]
+    fn test_qc() {
+        // MOD11 good with LST error class 1 (<= 2 K)
+        let qc = LstQc::decode(QcLayout::Mod11, 0b0100_0001);
+        assert_eq!(qc.mandatory, 1);
+        assert_eq!(qc.lst_error, 2.0);
+        assert!(qc.passes(Some(2.0)));
+        assert!(!qc.passes(Some(1.0)));
+        // MOD21 with excellent LST and emissivity accuracy
+        let qc = LstQc::decode(QcLayout::Mod21, 0b1111 << 12);
+        assert_eq!(qc.lst_error, 1.0);
+        assert_eq!(qc.emissivity_error, 0.01);
+        assert!(!LstQc::decode(QcLayout::Mod21, 2).is_produced());
+    }
+
+    #[test]
+    fn test_lst() {
+        // 300 K day, 290 K night
+        let mut day = vec![15000; 25];
+        day[1] = 0;
+        let lst = Lst {
+            product: ProductType::MOD11A2,
+            day: lst("LST_Day_1km", day),
+            night: lst("LST_Night_1km", vec![14500; 25]),
+            qc_day: lst("QC_Day", vec![0; 25]),
+            qc_night: lst("QC_Night", {
+                let mut qc = vec![0; 25];
+                qc[2] = 2;
+                qc
+            }),
+        };
+        let date = ModisDate::parse("A2024217").unwrap();
+        let day = lst
+            .series(Overpass::Day, 0, 0, TemperatureUnit::Celsius, None)
+            .unwrap();
+        assert!((day.get(date).unwrap() - 26.85).abs() < 1e-9);
+        let fill = lst
+            .series(Overpass::Day, 0, 1, TemperatureUnit::Kelvin, None)
+            .unwrap();
+        assert_eq!(fill.get(date), None);
+
+        let diff = lst.day_night_difference(0, 0, None).unwrap();
+        assert!((diff.get(date).unwrap() - 10.0).abs() < 1e-9);
+        // Cloudy night
+        assert_eq!(
+            lst.day_night_difference(0, 2, None).unwrap().get(date),
+            None
+        );
+
+        let aqua = TimeSeries::new(vec![(date, Some(30.0))]);
+        let mean = terra_aqua_mean(&day, &aqua, true);
+        assert!((mean.get(date).unwrap() - 28.425).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,120 @@
+// Fill and valid range masking of raw band values
+// The subset endpoint doesn't report fill values, they come from crate::bands
+// or the built in table of common bands below
+
+use crate::structs::{Band, ModisData};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Mask {
+    pub fill_values: Vec<i32>,
+    pub valid_range: Option<(i32, i32)>,
+}
+
+impl Mask {
+    /// No masking, every value is valid
+    pub fn none() -> Mask {
+        Mask::default()
+    }
+
+    pub fn from_band(band: &Band) -> Mask {
+        Mask {
+            fill_values: band.fill_value().into_iter().collect(),
+            valid_range: band.valid_range(),
+        }
+    }
+
+    /// Built in fill values and valid ranges for common bands, no masking for unknown bands
+    pub fn for_band(band: &str) -> Mask {
+        let (fill_values, valid_range): (&[i32], Option<(i32, i32)>) = match band {
+            "Lai_500m" | "Fpar_500m" | "Lai" | "Fpar" => (&[255], Some((0, 100))),
+            "LaiStdDev_500m" | "FparStdDev_500m" | "LaiStdDev" | "FparStdDev" => {
+                (&[255], Some((0, 100)))
+            }
+            "250m_16_days_NDVI" | "250m_16_days_EVI" | "500m_16_days_NDVI" | "500m_16_days_EVI" => {
+                (&[-3000], Some((-2000, 10000)))
+            }
+            "LST_Day_1km" | "LST_Night_1km" | "LST_1KM" | "LST_Day_1KM" | "LST_Night_1KM" => {
+                (&[0], Some((7500, 65535)))
+            }
+            "Gpp_500m" | "PsnNet_500m" => (&[32767], Some((0, 30000))),
+            "ET_500m" | "LE_500m" | "PET_500m" | "PLE_500m" => (&[32767], Some((-32767, 32700))),
+            "sur_refl_b01" | "sur_refl_b02" | "sur_refl_b03" | "sur_refl_b04" | "sur_refl_b05"
+            | "sur_refl_b06" | "sur_refl_b07" => (&[-28672], Some((-100, 16000))),
+            b if b.starts_with("SurfReflect_I") || b.starts_with("SurfReflect_M") => {
+                (&[-28672], Some((-100, 16000)))
+            }
+            // Spectral indices from crate::indices
+            "NDVI" | "EVI" | "NBR" | "NDWI" | "NDMI" | "SAVI" | "NIRv" => (&[-28672], None),
+            "LC_Type1" => (&[255], Some((1, 17))),
+            "LC_Type2" => (&[255], Some((0, 15))),
+            "LC_Type3" => (&[255], Some((0, 10))),
+            "LC_Type4" => (&[255], Some((0, 8))),
+            "LC_Type5" => (&[255], Some((0, 11))),
+            "LC_Prop1" | "LC_Prop2" | "LC_Prop3" => (&[255], Some((1, 51))),
+            "Burn_Date" => (&[-1, -2], Some((0, 366))),
+            "FireMask" => (&[], Some((0, 9))),
+            _ => (&[], None),
+        };
+        Mask {
+            fill_values: fill_values.to_vec(),
+            valid_range,
+        }
+    }
+
+    pub fn is_valid(&self, value: i32) -> bool {
+        if self.fill_values.contains(&value) {
+            return false;
+        }
+        match self.valid_range {
+            Some((min, max)) => value >= min && value <= max,
+            None => true,
+        }
+    }
+}
+
+impl ModisData {
+    /// Built in mask for this band, see [`Mask::for_band`]
+    pub fn mask(&self) -> Mask {
+        Mask::for_band(&self.band)
+    }
+
+    /// Scaled value, None if masked
+    pub fn masked_value(&self, value: i32, mask: &Mask) -> Option<f64> {
+        if mask.is_valid(value) {
+            Some(self.scale_value(value))
+        } else {
+            None
+        }
+    }
+}
+
+/// QC band filter, a pixel passes when its QC value for the same date satisfies `pass`
+/// Pixels without a QC value for that date fail
+pub struct QcFilter<'a> {
+    pub qc: &'a ModisData,
+    pub pass: Box<dyn Fn(i32) -> bool + 'a>,
+}
+
+impl<'a> QcFilter<'a> {
+    pub fn new<F: Fn(i32) -> bool + 'a>(qc: &'a ModisData, pass: F) -> QcFilter<'a> {
+        QcFilter {
+            qc,
+            pass: Box::new(pass),
+        }
+    }
+
+    /// QC value for the pixel at `index` on `modis_date`
+    pub fn value(&self, modis_date: &str, index: usize) -> Option<i32> {
+        self.qc
+            .subset
+            .iter()
+            .find(|s| s.modis_date == modis_date)
+            .and_then(|s| s.data.get(index).copied())
+    }
+
+    pub fn passes(&self, modis_date: &str, index: usize) -> bool {
+        self.value(modis_date, index)
+            .map(|v| (self.pass)(v))
+            .unwrap_or(false)
+    }
+}
@@ -0,0 +1,194 @@
+// Combine subsets from different locations onto one grid
+//
+// Pixel edges of the global MODIS sinusoidal grid fall on whole multiples of the cell size
+// (the grid origin is -20015109.354, 10007554.677 which is a whole number of cells), so
+// subsets are aligned by rounding their corners to cell counts.
+
+use std::collections::BTreeMap;
+
+use crate::structs::{ModisData, Subset};
+
+/// Value written where no input covers a pixel, the band's fill value when it has a known one
+pub fn nodata_value(data: &ModisData) -> i32 {
+    data.mask().fill_values.first().copied().unwrap_or(i32::MIN)
+}
+
+/// How overlapping valid values are combined
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overlap {
+    /// First valid value in input order
+    Priority,
+    /// Mean of the valid values rounded to the raw integer, not meaningful for class or QC bands
+    Mean,
+    Max,
+    Min,
+}
+
+// Global (row from the top, col) of the upper left pixel, errors off the sinusoidal grid
+fn origin(data: &ModisData) -> Result<(i64, i64), Box<dyn std::error::Error>> {
+    let top = data.yll() + data.nrows as f64 * data.cellsize;
+    let (row, col) = (-top / data.cellsize, data.xll() / data.cellsize);
+    // Corners are printed to 2 decimals, allow a little more than that
+    if (row - row.round()).abs() > 0.01 || (col - col.round()).abs() > 0.01 {
+        return Err(format!(
+            "corner {}, {} is not on the {} m sinusoidal grid",
+            data.xllcorner, data.yllcorner, data.cellsize
+        )
+        .into());
+    }
+    Ok((row.round() as i64, col.round() as i64))
+}
+
+// Valid values and the first raw code seen for an output pixel
+type Cell = (Vec<i32>, Option<i32>);
+
+/// Mosaic subsets of one band onto the shared sinusoidal grid
+///
+/// The output covers every input and has every date found in any of them, pixels where
+/// overlapping inputs are valid are combined with `overlap`. Where none is valid the first
+/// input's raw code is kept (e.g. 254 for water), [`nodata_value`] is written where no input
+/// covers the pixel.
+pub fn mosaic(
+    data: &[ModisData],
+    overlap: Overlap,
+) -> Result<ModisData, Box<dyn std::error::Error>> {
+    let first = data.first().ok_or("nothing to mosaic")?;
+    for other in data {
+        if (other.cellsize - first.cellsize).abs() > 1e-6 {
+            return Err(format!(
+                "cannot mosaic cell sizes {} and {}",
+                first.cellsize, other.cellsize
+            )
+            .into());
+        }
+        if other.band != first.band {
+            return Err(format!("cannot mosaic bands {} and {}", first.band, other.band).into());
+        }
+    }
+
+    let cellsize = first.cellsize;
+    let origins = data.iter().map(origin).collect::<Result<Vec<_>, _>>()?;
+    let top = origins.iter().map(|o| o.0).min().unwrap();
+    let left = origins.iter().map(|o| o.1).min().unwrap();
+    let bottom = data
+        .iter()
+        .zip(&origins)
+        .map(|(d, o)| o.0 + d.nrows as i64)
+        .max()
+        .unwrap();
+    let right = data
+        .iter()
+        .zip(&origins)
+        .map(|(d, o)| o.1 + d.ncols as i64)
+        .max()
+        .unwrap();
+    let (nrows, ncols) = ((bottom - top) as usize, (right - left) as usize);
+    let nodata = nodata_value(first);
+
+    // modis_date -> (template subset, (valid values, first raw code) per output pixel)
+    let mut dates: BTreeMap<String, (Subset, Vec<Cell>)> = BTreeMap::new();
+    for (input, (row0, col0)) in data.iter().zip(&origins) {
+        let mask = input.mask();
+        let in_cols = input.ncols.max(0) as usize;
+        for subset in &input.subset {
+            let (_, out) = dates
+                .entry(subset.modis_date.clone())
+                .or_insert_with(|| (subset.clone(), vec![(Vec::new(), None); nrows * ncols]));
+            for (i, value) in subset.data.iter().enumerate() {
+                let row = (row0 - top) as usize + i / in_cols;
+                let col = (col0 - left) as usize + i % in_cols;
+                let (valid, raw) = &mut out[row * ncols + col];
+                if mask.is_valid(*value) {
+                    valid.push(*value);
+                }
+                raw.get_or_insert(*value);
+            }
+        }
+    }
+
+    let combine = |(values, raw): &Cell| -> i32 {
+        match overlap {
+            _ if values.is_empty() => raw.unwrap_or(nodata),
+            Overlap::Priority => values[0],
+            Overlap::Mean => {
+                let sum: f64 = values.iter().map(|v| *v as f64).sum();
+                (sum / values.len() as f64).round() as i32
+            }
+            Overlap::Max => *values.iter().max().unwrap(),
+            Overlap::Min => *values.iter().min().unwrap(),
+        }
+    };
+    let mut subset: Vec<Subset> = dates
+        .into_values()
+        .map(|(template, values)| Subset {
+            data: values.iter().map(combine).collect(),
+            ..template
+        })
+        .collect();
+    subset.sort_by_key(|s| s.date());
+
+    Ok(ModisData {
+        xllcorner: format!("{:.2}", left as f64 * cellsize),
+        yllcorner: format!("{:.2}", -(bottom as f64) * cellsize),
+        nrows: nrows as i32,
+        ncols: ncols as i32,
+        subset,
+        ..first.clone()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::otago;
+
+    // The Otago grid shifted by whole pixels
+    fn shifted(rows: i32, cols: i32) -> ModisData {
+        let mut data = otago();
+        data.xllcorner = format!("{:.2}", data.xll() + cols as f64 * data.cellsize);
+        data.yllcorner = format!("{:.2}", data.yll() - rows as f64 * data.cellsize);
+        data
+    }
+
+    #[test]
+    fn test_mosaic() {
+        let a = otago();
+        let mut b = shifted(2, 3);
+        b.subset[0].data = vec![7; 25];
+        let mut later = b.subset[0].clone();
+        later.modis_date = "A2024225".to_string();
+        later.calendar_date = "2024-08-12".to_string();
+        b.subset.push(later);
+
+        let m = mosaic(&[a.clone(), b.clone()], Overlap::Priority).unwrap();
+        assert_eq!((m.nrows, m.ncols), (7, 8));
+        assert!((m.xll() - a.xll()).abs() < 1.0);
+        assert!((m.yll() - (a.yll() - 2.0 * a.cellsize)).abs() < 1.0);
+        assert_eq!(m.subset.len(), 2);
+        // Upper left from a, lower right from b, the overlap keeps a except where a is invalid
+        let first = &m.subset[0].data;
+        assert_eq!(first[0], 13);
+        assert_eq!(first[6 * 8 + 7], 7);
+        assert_eq!(first[2 * 8 + 3], 3);
+        assert_eq!(first[4 * 8 + 3], 7);
+        assert_eq!(first[6 * 8], 255);
+        // Water/fill code with no valid overlap is carried through
+        assert_eq!(first[8 + 3], 254);
+        // Second date only has b
+        assert_eq!(m.subset[1].data[0], 255);
+
+        // Overlap of 9 (a) and 7 (b)
+        let mean = mosaic(&[a.clone(), b.clone()], Overlap::Mean).unwrap();
+        assert_eq!(mean.subset[0].data[2 * 8 + 4], 8);
+        let max = mosaic(&[b.clone(), a.clone()], Overlap::Max).unwrap();
+        assert_eq!(max.subset[0].data[2 * 8 + 4], 9);
+        assert_eq!(max.subset.len(), 2);
+
+        let mut other = otago();
+        other.cellsize = 231.656358264;
+        assert!(mosaic(&[a.clone(), other], Overlap::Priority).is_err());
+        let mut off_grid = b;
+        off_grid.xllcorner = format!("{:.2}", off_grid.xll() + 100.0);
+        assert!(mosaic(&[a, off_grid], Overlap::Priority).is_err());
+    }
+}
@@ -0,0 +1,393 @@
+// NetCDF-CF export of a ModisData time cube
+// Written directly in the NetCDF classic 64-bit offset format (CDF-2), so no libnetcdf is needed
+// https://docs.unidata.ucar.edu/netcdf-c/current/file_format_specifications.html
+
+use std::io::Write;
+
+use crate::sinusoidal::EARTH_RADIUS;
+use crate::structs::{Band, ModisData};
+
+const NC_DIMENSION: u32 = 0x0A;
+const NC_VARIABLE: u32 = 0x0B;
+const NC_ATTRIBUTE: u32 = 0x0C;
+
+const NC_CHAR: u32 = 2;
+const NC_INT: u32 = 4;
+const NC_DOUBLE: u32 = 6;
+
+pub const ORNL_CITATION: &str = "ORNL DAAC. 2018. MODIS and VIIRS Land Products Global Subsetting and Visualization Tool. ORNL DAAC, Oak Ridge, Tennessee, USA. https://doi.org/10.3334/ORNLDAAC/1379";
+
+enum Attr {
+    Text(String),
+    Int(Vec<i32>),
+    Double(Vec<f64>),
+}
+
+enum Values {
+    Int(Vec<i32>),
+    Double(Vec<f64>),
+}
+
+struct Var {
+    name: String,
+    dims: Vec<u32>,
+    attrs: Vec<(String, Attr)>,
+    values: Values,
+}
+
+impl Var {
+    fn nc_type(&self) -> u32 {
+        match self.values {
+            Values::Int(_) => NC_INT,
+            Values::Double(_) => NC_DOUBLE,
+        }
+    }
+
+    fn data(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        match &self.values {
+            Values::Int(v) => v.iter().for_each(|x| out.extend(x.to_be_bytes())),
+            Values::Double(v) => v.iter().for_each(|x| out.extend(x.to_be_bytes())),
+        }
+        pad(&mut out);
+        out
+    }
+}
+
+fn pad(out: &mut Vec<u8>) {
+    while !out.len().is_multiple_of(4) {
+        out.push(0);
+    }
+}
+
+fn put_u32(out: &mut Vec<u8>, v: u32) {
+    out.extend(v.to_be_bytes());
+}
+
+fn put_name(out: &mut Vec<u8>, name: &str) {
+    put_u32(out, name.len() as u32);
+    out.extend(name.as_bytes());
+    pad(out);
+}
+
+fn put_attrs(out: &mut Vec<u8>, attrs: &[(String, Attr)]) {
+    if attrs.is_empty() {
+        put_u32(out, 0);
+        put_u32(out, 0);
+        return;
+    }
+    put_u32(out, NC_ATTRIBUTE);
+    put_u32(out, attrs.len() as u32);
+    for (name, attr) in attrs {
+        put_name(out, name);
+        match attr {
+            Attr::Text(s) => {
+                put_u32(out, NC_CHAR);
+                put_u32(out, s.len() as u32);
+                out.extend(s.as_bytes());
+            }
+            Attr::Int(v) => {
+                put_u32(out, NC_INT);
+                put_u32(out, v.len() as u32);
+                v.iter().for_each(|x| out.extend(x.to_be_bytes()));
+            }
+            Attr::Double(v) => {
+                put_u32(out, NC_DOUBLE);
+                put_u32(out, v.len() as u32);
+                v.iter().for_each(|x| out.extend(x.to_be_bytes()));
+            }
+        }
+        pad(out);
+    }
+}
+
+fn header(
+    dims: &[(&str, u32)],
+    global: &[(String, Attr)],
+    vars: &[Var],
+    begins: &[u64],
+) -> Vec<u8> {
+    let mut out = b"CDF\x02".to_vec();
+    // numrecs, there is no record dimension
+    put_u32(&mut out, 0);
+
+    put_u32(&mut out, NC_DIMENSION);
+    put_u32(&mut out, dims.len() as u32);
+    for (name, len) in dims {
+        put_name(&mut out, name);
+        put_u32(&mut out, *len);
+    }
+
+    put_attrs(&mut out, global);
+
+    put_u32(&mut out, NC_VARIABLE);
+    put_u32(&mut out, vars.len() as u32);
+    for (var, begin) in vars.iter().zip(begins) {
+        put_name(&mut out, &var.name);
+        put_u32(&mut out, var.dims.len() as u32);
+        var.dims.iter().for_each(|d| put_u32(&mut out, *d));
+        put_attrs(&mut out, &var.attrs);
+        put_u32(&mut out, var.nc_type());
+        put_u32(&mut out, var.data().len() as u32);
+        out.extend(begin.to_be_bytes());
+    }
+    out
+}
+
+// NetCDF names should start with a letter, MODIS band names often start with a digit
+fn variable_name(band: &str) -> String {
+    let name: String = band
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        name
+    } else {
+        format!("band_{}", name)
+    }
+}
+
+fn text(s: &str) -> Attr {
+    Attr::Text(s.to_string())
+}
+
+impl ModisData {
+    /// ORNL citation for this subset
+    pub fn citation(&self) -> String {
+        format!(
+            "{} Subset obtained for {} product at {},{}, time period: {} to {}, and subset size: {} x {} pixels.",
+            ORNL_CITATION,
+            self.product().unwrap_or("unknown"),
+            self.latitude,
+            self.longitude,
+            self.subset.first().map(|s| s.calendar_date.as_str()).unwrap_or(""),
+            self.subset.last().map(|s| s.calendar_date.as_str()).unwrap_or(""),
+            self.ncols,
+            self.nrows,
+        )
+    }
+
+    /// Write a CF-1.8 NetCDF file with `time`, `y`, `x` dimensions
+    ///
+    /// `band` is the metadata from [`crate::bands`], used for `_FillValue` and `valid_range`,
+    /// which fall back to the built in [`ModisData::mask`] and the scale factor to `scale`
+    /// when there is no band metadata. Errors when there are no subsets.
+    pub fn write_netcdf<W: Write>(
+        &self,
+        mut writer: W,
+        band: Option<&Band>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if self.subset.is_empty() {
+            return Err("no subsets to write".into());
+        }
+        let nrows = self.nrows.max(0) as usize;
+        let ncols = self.ncols.max(0) as usize;
+
+        let mut time = Vec::with_capacity(self.subset.len());
+        let mut values = Vec::with_capacity(self.subset.len() * nrows * ncols);
+        for subset in &self.subset {
+            let date = subset
+                .date()
+                .ok_or_else(|| format!("invalid date {:?}", subset.modis_date))?;
+            if subset.data.len() != nrows * ncols {
+                return Err(format!(
+                    "{} has {} values, expected {}",
+                    subset.modis_date,
+                    subset.data.len(),
+                    nrows * ncols
+                )
+                .into());
+            }
+            let epoch = chrono::NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
+            time.push((date.date() - epoch).num_days() as f64);
+            values.extend(&subset.data);
+        }
+
+        let x: Vec<f64> = (0..ncols).map(|c| self.pixel_center(0, c).0).collect();
+        let y: Vec<f64> = (0..nrows).map(|r| self.pixel_center(r, 0).1).collect();
+        let mut lat = Vec::with_capacity(nrows * ncols);
+        let mut lon = Vec::with_capacity(nrows * ncols);
+        for row in 0..nrows {
+            for col in 0..ncols {
+                let (la, lo) = self.pixel_lat_lon(row, col);
+                lat.push(la);
+                lon.push(lo);
+            }
+        }
+
+        let dims = [
+            ("time", self.subset.len() as u32),
+            ("y", nrows as u32),
+            ("x", ncols as u32),
+        ];
+
+        let mut band_attrs = vec![
+            ("long_name".to_string(), text(&self.band)),
+            ("units".to_string(), text(&self.units)),
+            ("grid_mapping".to_string(), text("crs")),
+            ("coordinates".to_string(), text("lat lon")),
+        ];
+        let scale = band.and_then(|b| b.scale_factor()).or(self.scale_factor());
+        if let Some(scale) = scale {
+            band_attrs.push(("scale_factor".to_string(), Attr::Double(vec![scale])));
+        }
+        let mask = self.mask();
+        let fill = band
+            .and_then(|b| b.fill_value())
+            .or(mask.fill_values.first().copied());
+        if let Some(fill) = fill {
+            band_attrs.push(("_FillValue".to_string(), Attr::Int(vec![fill])));
+        }
+        let valid_range = band.and_then(|b| b.valid_range()).or(mask.valid_range);
+        if let Some((min, max)) = valid_range {
+            band_attrs.push(("valid_range".to_string(), Attr::Int(vec![min, max])));
+        }
+
+        let vars = vec![
+            Var {
+                name: "time".to_string(),
+                dims: vec![0],
+                attrs: vec![
+                    ("standard_name".to_string(), text("time")),
+                    ("units".to_string(), text("days since 1970-01-01")),
+                    ("calendar".to_string(), text("standard")),
+                ],
+                values: Values::Double(time),
+            },
+            Var {
+                name: "y".to_string(),
+                dims: vec![1],
+                attrs: vec![
+                    ("standard_name".to_string(), text("projection_y_coordinate")),
+                    ("units".to_string(), text("m")),
+                ],
+                values: Values::Double(y),
+            },
+            Var {
+                name: "x".to_string(),
+                dims: vec![2],
+                attrs: vec![
+                    ("standard_name".to_string(), text("projection_x_coordinate")),
+                    ("units".to_string(), text("m")),
+                ],
+                values: Values::Double(x),
+            },
+            Var {
+                name: "lat".to_string(),
+                dims: vec![1, 2],
+                attrs: vec![
+                    ("standard_name".to_string(), text("latitude")),
+                    ("units".to_string(), text("degrees_north")),
+                ],
+                values: Values::Double(lat),
+            },
+            Var {
+                name: "lon".to_string(),
+                dims: vec![1, 2],
+                attrs: vec![
+                    ("standard_name".to_string(), text("longitude")),
+                    ("units".to_string(), text("degrees_east")),
+                ],
+                values: Values::Double(lon),
+            },
+            Var {
+                name: "crs".to_string(),
+                dims: vec![],
+                attrs: vec![
+                    ("grid_mapping_name".to_string(), text("sinusoidal")),
+                    (
+                        "longitude_of_central_meridian".to_string(),
+                        Attr::Double(vec![0.0]),
+                    ),
+                    ("false_easting".to_string(), Attr::Double(vec![0.0])),
+                    ("false_northing".to_string(), Attr::Double(vec![0.0])),
+                    ("earth_radius".to_string(), Attr::Double(vec![EARTH_RADIUS])),
+                    ("xllcorner".to_string(), text(&self.xllcorner)),
+                    ("yllcorner".to_string(), text(&self.yllcorner)),
+                    ("cellsize".to_string(), Attr::Double(vec![self.cellsize])),
+                ],
+                values: Values::Int(vec![0]),
+            },
+            Var {
+                name: variable_name(&self.band),
+                dims: vec![0, 1, 2],
+                attrs: band_attrs,
+                values: Values::Int(values),
+            },
+        ];
+
+        let global = vec![
+            ("Conventions".to_string(), text("CF-1.8")),
+            (
+                "title".to_string(),
+                text(&format!(
+                    "{} {} subset",
+                    self.product().unwrap_or(""),
+                    self.band
+                )),
+            ),
+            (
+                "source".to_string(),
+                text("MODIS/VIIRS Land Product Subsets, ORNL DAAC"),
+            ),
+            ("source_url".to_string(), text(&self.header)),
+            ("references".to_string(), text(&self.citation())),
+        ];
+
+        // The header length doesn't depend on the offsets, so size it first
+        let mut begins = vec![0u64; vars.len()];
+        let mut offset = header(&dims, &global, &vars, &begins).len() as u64;
+        let data: Vec<Vec<u8>> = vars.iter().map(|v| v.data()).collect();
+        for (begin, d) in begins.iter_mut().zip(&data) {
+            *begin = offset;
+            offset += d.len() as u64;
+        }
+
+        writer.write_all(&header(&dims, &global, &vars, &begins))?;
+        for d in data {
+            writer.write_all(&d)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::otago;
+
+    #[test]
+    fn test_write_netcdf() {
+        let data = otago();
+        let band = Band {
+            band: "Lai_500m".to_string(),
+            description: "Leaf area index".to_string(),
+            units: "m^2/m^2".to_string(),
+            scale_factor: Some("0.1".to_string()),
+            fill_value: Some("255".to_string()),
+            valid_range: Some("0 to 100".to_string()),
+        };
+        let mut out = Vec::new();
+        data.write_netcdf(&mut out, Some(&band)).unwrap();
+
+        assert_eq!(&out[..4], b"CDF\x02");
+        // The band variable is written last, its 25 values end the file
+        let tail: Vec<i32> = out[out.len() - 100..]
+            .chunks(4)
+            .map(|c| i32::from_be_bytes([c[0], c[1], c[2], c[3]]))
+            .collect();
+        assert_eq!(tail, data.subset[0].data);
+        assert_eq!(variable_name("250m_16_days_NDVI"), "band_250m_16_days_NDVI");
+
+        // Without band metadata the fill value and valid range come from the built in mask
+        let mut plain = Vec::new();
+        data.write_netcdf(&mut plain, None).unwrap();
+        assert!(plain.windows(10).any(|w| w == b"_FillValue"));
+        assert!(plain.windows(11).any(|w| w == b"valid_range"));
+
+        let mut empty = data.clone();
+        empty.subset.clear();
+        assert!(empty.write_netcdf(Vec::new(), None).is_err());
+    }
+}
@@ -0,0 +1,239 @@
+// Terra (MOD) and Aqua (MYD) twin products
+//
+// Twins share band names and grids. The 16-day MOD13Q1/MYD13Q1 composites are offset by
+// 8 days so merging them doubles the temporal density, the 8-day products land on the same
+// dates and need a reducer for the coincident pair.
+
+use serde::Serialize;
+
+use crate::dates::ModisDate;
+use crate::structs::{ModisData, ProductType};
+use crate::timeseries::TimeSeries;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+pub enum Platform {
+    Terra,
+    Aqua,
+}
+
+impl ProductType {
+    /// Terra for MOD products, Aqua for MYD products
+    pub fn platform(&self) -> Option<Platform> {
+        let name: &str = (*self).into();
+        if name.starts_with("MOD") {
+            Some(Platform::Terra)
+        } else if name.starts_with("MYD") {
+            Some(Platform::Aqua)
+        } else {
+            None
+        }
+    }
+
+    /// The same product from the other platform, MOD13Q1 <-> MYD13Q1
+    pub fn twin(&self) -> Option<ProductType> {
+        let name: &str = (*self).into();
+        let other = match self.platform()? {
+            Platform::Terra => name.replacen("MOD", "MYD", 1),
+            Platform::Aqua => name.replacen("MYD", "MOD", 1),
+        };
+        other.parse().ok()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Observation {
+    pub date: ModisDate,
+    pub platform: Platform,
+    pub value: Option<f64>,
+}
+
+/// How to combine Terra and Aqua observations on the same date
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Coincident {
+    /// Mean of the valid values
+    Mean,
+    Max,
+    Min,
+    /// This platform's value, the other one's when it is masked
+    Prefer(Platform),
+}
+
+/// Merged Terra and Aqua series, ordered by date then platform
+#[derive(Debug, Clone, PartialEq, Default, Serialize)]
+pub struct PlatformSeries {
+    pub observations: Vec<Observation>,
+}
+
+impl PlatformSeries {
+    pub fn merge(terra: &TimeSeries, aqua: &TimeSeries) -> PlatformSeries {
+        let tag = |series: &TimeSeries, platform| {
+            series
+                .iter()
+                .map(move |(date, value)| Observation {
+                    date: *date,
+                    platform,
+                    value: *value,
+                })
+                .collect::<Vec<_>>()
+        };
+        let mut observations = tag(terra, Platform::Terra);
+        observations.extend(tag(aqua, Platform::Aqua));
+        observations.sort_by_key(|o| (o.date, o.platform));
+        PlatformSeries { observations }
+    }
+
+    /// Only one platform's observations
+    pub fn platform(&self, platform: Platform) -> TimeSeries {
+        self.observations
+            .iter()
+            .filter(|o| o.platform == platform)
+            .map(|o| (o.date, o.value))
+            .collect()
+    }
+
+    /// One value per date, coincident observations combined with `reducer`
+    pub fn reduce(&self, reducer: Coincident) -> TimeSeries {
+        let mut points = Vec::new();
+        for pair in self.observations.chunk_by(|a, b| a.date == b.date) {
+            let valid: Vec<(Platform, f64)> = pair
+                .iter()
+                .filter_map(|o| o.value.map(|v| (o.platform, v)))
+                .collect();
+            let values = valid.iter().map(|(_, v)| *v);
+            let value = match reducer {
+                Coincident::Mean if !valid.is_empty() => {
+                    Some(values.sum::<f64>() / valid.len() as f64)
+                }
+                Coincident::Mean => None,
+                Coincident::Max => values.reduce(f64::max),
+                Coincident::Min => values.reduce(f64::min),
+                Coincident::Prefer(platform) => valid
+                    .iter()
+                    .find(|(p, _)| *p == platform)
+                    .or(valid.first())
+                    .map(|(_, v)| *v),
+            };
+            points.push((pair[0].date, value));
+        }
+        TimeSeries::new(points)
+    }
+}
+
+/// The same band and window from both platforms
+#[derive(Debug, Clone)]
+pub struct TerraAqua {
+    pub terra: ModisData,
+    pub aqua: ModisData,
+}
+
+impl TerraAqua {
+    pub fn new(terra: ModisData, aqua: ModisData) -> Result<TerraAqua, Box<dyn std::error::Error>> {
+        if !terra.same_grid(&aqua) || terra.band != aqua.band {
+            return Err("Terra and Aqua data must be the same band on the same grid".into());
+        }
+        Ok(TerraAqua { terra, aqua })
+    }
+
+    /// Fetch `band` for `product` and its twin through [`crate::subset`], either platform
+    /// can be given
+    #[allow(clippy::too_many_arguments)]
+    pub async fn fetch(
+        product: ProductType,
+        latitude: f64,
+        longitude: f64,
+        band: &str,
+        start_date: &str,
+        end_date: &str,
+        km_above_below: u8,
+        km_left_right: u8,
+    ) -> Result<TerraAqua, Box<dyn std::error::Error>> {
+        let twin = product.twin().ok_or("product has no Terra/Aqua twin")?;
+        let (terra, aqua) = match product.platform() {
+            Some(Platform::Terra) => (product, twin),
+            _ => (twin, product),
+        };
+        let mut data = Vec::new();
+        for p in [terra, aqua] {
+            data.push(
+                crate::subset(
+                    p.into(),
+                    latitude,
+                    longitude,
+                    band,
+                    start_date,
+                    end_date,
+                    km_above_below,
+                    km_left_right,
+                )
+                .await?,
+            );
+        }
+        let aqua = data.pop().unwrap();
+        let terra = data.pop().unwrap();
+        TerraAqua::new(terra, aqua)
+    }
+
+    /// Merged series of a pixel with the built in band mask
+    pub fn pixel_series(&self, row: usize, col: usize) -> Option<PlatformSeries> {
+        Some(PlatformSeries::merge(
+            &self.terra.pixel_series(row, col)?,
+            &self.aqua.pixel_series(row, col)?,
+        ))
+    }
+
+    /// ((row, col), merged series) for every pixel, row-major
+    pub fn pixel_series_iter(&self) -> impl Iterator<Item = ((usize, usize), PlatformSeries)> + '_ {
+        self.terra
+            .pixel_series_iter()
+            .zip(self.aqua.pixel_series_iter())
+            .map(|((pixel, terra), (_, aqua))| (pixel, PlatformSeries::merge(&terra, &aqua)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn series(doys: &[(u32, Option<f64>)]) -> TimeSeries {
+        doys.iter()
+            .map(|(doy, v)| (ModisDate::from_year_doy(2024, *doy).unwrap(), *v))
+            .collect()
+    }
+
+    #[test]
+    fn test_twin() {
+        assert_eq!(ProductType::MOD13Q1.twin(), Some(ProductType::MYD13Q1));
+        assert_eq!(
+            ProductType::MYD17A2HGF.twin(),
+            Some(ProductType::MOD17A2HGF)
+        );
+        assert_eq!(ProductType::MOD44B.twin(), None);
+        assert_eq!(ProductType::VNP13A1.platform(), None);
+    }
+
+    #[test]
+    fn test_merge() {
+        // 16-day composites offset by 8 days interleave
+        let terra = series(&[(1, Some(0.5)), (17, Some(0.6))]);
+        let aqua = series(&[(9, Some(0.55)), (25, None)]);
+        let merged = PlatformSeries::merge(&terra, &aqua);
+        assert_eq!(merged.observations.len(), 4);
+        assert_eq!(merged.observations[1].platform, Platform::Aqua);
+        assert_eq!(merged.reduce(Coincident::Mean).len(), 4);
+
+        // Coincident 8-day composites
+        let terra = series(&[(1, Some(300.0)), (9, None)]);
+        let aqua = series(&[(1, Some(302.0)), (9, Some(298.0))]);
+        let merged = PlatformSeries::merge(&terra, &aqua);
+        let mean = merged.reduce(Coincident::Mean);
+        assert_eq!(mean.len(), 2);
+        assert_eq!(mean.points[0].1, Some(301.0));
+        assert_eq!(mean.points[1].1, Some(298.0));
+        assert_eq!(merged.reduce(Coincident::Max).points[0].1, Some(302.0));
+        assert_eq!(
+            merged.reduce(Coincident::Prefer(Platform::Terra)).points[1].1,
+            Some(298.0)
+        );
+        assert_eq!(merged.platform(Platform::Terra), terra);
+    }
+}
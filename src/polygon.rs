@@ -0,0 +1,392 @@
+// Polygons (GeoJSON lon/lat) and their per-pixel coverage on a subset grid
+//
+// Coverage is worked out in sinusoidal metres, where pixels are exact squares. Polygon
+// edges are straight in lon/lat so they are densified before projecting.
+
+use serde_json::Value;
+
+use crate::sinusoidal;
+use crate::structs::ModisData;
+
+// Points per polygon edge when projecting
+const DENSIFY: usize = 8;
+
+/// Closed ring of (x, y) points
+pub type Ring = Vec<(f64, f64)>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polygon {
+    /// (longitude, latitude) ring, as in GeoJSON
+    pub exterior: Vec<(f64, f64)>,
+    pub holes: Vec<Vec<(f64, f64)>>,
+}
+
+fn ring(value: &Value) -> Result<Vec<(f64, f64)>, Box<dyn std::error::Error>> {
+    value
+        .as_array()
+        .ok_or("ring is not an array")?
+        .iter()
+        .map(|p| {
+            match (
+                p.get(0).and_then(Value::as_f64),
+                p.get(1).and_then(Value::as_f64),
+            ) {
+                (Some(lon), Some(lat)) => Ok((lon, lat)),
+                _ => Err("bad coordinate".into()),
+            }
+        })
+        .collect()
+}
+
+fn polygon(rings: &Value) -> Result<Polygon, Box<dyn std::error::Error>> {
+    let rings = rings
+        .as_array()
+        .ok_or("polygon coordinates are not an array")?
+        .iter()
+        .map(ring)
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut rings = rings.into_iter();
+    Ok(Polygon {
+        exterior: rings.next().ok_or("polygon has no rings")?,
+        holes: rings.collect(),
+    })
+}
+
+// Nested WKT parentheses, leaves are coordinate lists
+enum Wkt {
+    List(Vec<Wkt>),
+    Points(Vec<(f64, f64)>),
+}
+
+fn wkt_list(
+    chars: &mut std::iter::Peekable<std::str::Chars<'_>>,
+) -> Result<Wkt, Box<dyn std::error::Error>> {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+    if chars.next() != Some('(') {
+        return Err("expected ( in WKT".into());
+    }
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+    if chars.peek() == Some(&'(') {
+        let mut items = Vec::new();
+        loop {
+            items.push(wkt_list(chars)?);
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            match chars.next() {
+                Some(',') => continue,
+                Some(')') => return Ok(Wkt::List(items)),
+                _ => return Err("expected , or ) in WKT".into()),
+            }
+        }
+    }
+    let text: String = chars.by_ref().take_while(|c| *c != ')').collect();
+    let points = text
+        .split(',')
+        .map(|point| {
+            let mut xy = point.split_whitespace().map(str::parse::<f64>);
+            match (xy.next(), xy.next()) {
+                (Some(Ok(x)), Some(Ok(y))) => Ok((x, y)),
+                _ => Err(format!("bad WKT coordinate {:?}", point.trim()).into()),
+            }
+        })
+        .collect::<Result<_, Box<dyn std::error::Error>>>()?;
+    Ok(Wkt::Points(points))
+}
+
+fn wkt_polygon(tree: Wkt) -> Result<Polygon, Box<dyn std::error::Error>> {
+    let Wkt::List(rings) = tree else {
+        return Err("WKT polygon needs a list of rings".into());
+    };
+    let mut rings = rings.into_iter().map(|ring| match ring {
+        Wkt::Points(points) => Ok(points),
+        Wkt::List(_) => Err("WKT ring nested too deeply"),
+    });
+    Ok(Polygon {
+        exterior: rings.next().ok_or("polygon has no rings")??,
+        holes: rings.collect::<Result<_, _>>()?,
+    })
+}
+
+impl Polygon {
+    /// Polygons from a GeoJSON Polygon, MultiPolygon, Feature, FeatureCollection or
+    /// GeometryCollection, other geometry types are skipped
+    pub fn from_geojson(value: &Value) -> Result<Vec<Polygon>, Box<dyn std::error::Error>> {
+        let kind = value
+            .get("type")
+            .and_then(Value::as_str)
+            .ok_or("missing type")?;
+        let coordinates = || value.get("coordinates").ok_or("missing coordinates");
+        Ok(match kind {
+            "Polygon" => vec![polygon(coordinates()?)?],
+            "MultiPolygon" => coordinates()?
+                .as_array()
+                .ok_or("coordinates are not an array")?
+                .iter()
+                .map(polygon)
+                .collect::<Result<_, _>>()?,
+            "Feature" => match value.get("geometry") {
+                Some(geometry) if !geometry.is_null() => Polygon::from_geojson(geometry)?,
+                _ => Vec::new(),
+            },
+            "FeatureCollection" | "GeometryCollection" => {
+                let key = if kind == "FeatureCollection" {
+                    "features"
+                } else {
+                    "geometries"
+                };
+                let mut polygons = Vec::new();
+                for item in value
+                    .get(key)
+                    .and_then(Value::as_array)
+                    .ok_or("missing members")?
+                {
+                    polygons.extend(Polygon::from_geojson(item)?);
+                }
+                polygons
+            }
+            _ => Vec::new(),
+        })
+    }
+
+    /// Polygons from WKT POLYGON or MULTIPOLYGON text in lon/lat, EMPTY gives none
+    pub fn from_wkt(wkt: &str) -> Result<Vec<Polygon>, Box<dyn std::error::Error>> {
+        let wkt = wkt.trim();
+        let split = wkt.find(|c: char| c == '(' || c.is_whitespace());
+        let (kind, rest) = wkt.split_at(split.unwrap_or(wkt.len()));
+        let rest = rest.trim();
+        if rest.eq_ignore_ascii_case("EMPTY") {
+            return Ok(Vec::new());
+        }
+        let mut chars = rest.chars().peekable();
+        let tree = wkt_list(&mut chars)?;
+        if chars.any(|c| !c.is_whitespace()) {
+            return Err("trailing characters after WKT geometry".into());
+        }
+        match (kind.to_uppercase().as_str(), tree) {
+            ("POLYGON", tree) => Ok(vec![wkt_polygon(tree)?]),
+            ("MULTIPOLYGON", Wkt::List(items)) => items.into_iter().map(wkt_polygon).collect(),
+            (kind, _) => Err(format!("unsupported WKT geometry {}", kind).into()),
+        }
+    }
+
+    /// (min_lon, min_lat, max_lon, max_lat) of several polygons
+    pub fn bbox(polygons: &[Polygon]) -> Option<(f64, f64, f64, f64)> {
+        let points = polygons.iter().flat_map(|p| p.exterior.iter());
+        points.fold(None, |acc, (lon, lat)| {
+            let (a, b, c, d) = acc.unwrap_or((*lon, *lat, *lon, *lat));
+            Some((a.min(*lon), b.min(*lat), c.max(*lon), d.max(*lat)))
+        })
+    }
+
+    /// Exterior and holes in sinusoidal x/y
+    pub fn to_sinusoidal(&self) -> (Ring, Vec<Ring>) {
+        (
+            project_ring(&self.exterior),
+            self.holes.iter().map(|h| project_ring(h)).collect(),
+        )
+    }
+}
+
+fn project_ring(ring: &[(f64, f64)]) -> Vec<(f64, f64)> {
+    let mut out = Vec::with_capacity(ring.len() * DENSIFY);
+    for w in ring.windows(2) {
+        let ((lon0, lat0), (lon1, lat1)) = (w[0], w[1]);
+        for s in 0..DENSIFY {
+            let t = s as f64 / DENSIFY as f64;
+            out.push(sinusoidal::to_sinusoidal(
+                lat0 + (lat1 - lat0) * t,
+                lon0 + (lon1 - lon0) * t,
+            ));
+        }
+    }
+    out
+}
+
+/// Even-odd point in ring test
+pub fn ring_contains(ring: &[(f64, f64)], x: f64, y: f64) -> bool {
+    let mut inside = false;
+    let n = ring.len();
+    for i in 0..n {
+        let (x0, y0) = ring[i];
+        let (x1, y1) = ring[(i + 1) % n];
+        if (y0 > y) != (y1 > y) && x < x0 + (y - y0) / (y1 - y0) * (x1 - x0) {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+/// Shoelace area, always positive
+pub fn ring_area(ring: &[(f64, f64)]) -> f64 {
+    let n = ring.len();
+    (0..n)
+        .map(|i| {
+            let (x0, y0) = ring[i];
+            let (x1, y1) = ring[(i + 1) % n];
+            x0 * y1 - x1 * y0
+        })
+        .sum::<f64>()
+        .abs()
+        / 2.0
+}
+
+/// Sutherland-Hodgman clip of a ring to an axis aligned box
+pub fn clip_ring(
+    ring: &[(f64, f64)],
+    xmin: f64,
+    ymin: f64,
+    xmax: f64,
+    ymax: f64,
+) -> Vec<(f64, f64)> {
+    // (inside test, intersection of an edge with the boundary)
+    type Edge = (fn(f64, f64, f64) -> bool, bool, f64);
+    let edges: [Edge; 4] = [
+        (|x, _, b| x >= b, true, xmin),
+        (|x, _, b| x <= b, true, xmax),
+        (|_, y, b| y >= b, false, ymin),
+        (|_, y, b| y <= b, false, ymax),
+    ];
+    let mut out = ring.to_vec();
+    for (inside, vertical, bound) in edges {
+        let input = std::mem::take(&mut out);
+        let n = input.len();
+        for i in 0..n {
+            let (px, py) = input[(i + n - 1) % n];
+            let (cx, cy) = input[i];
+            let cross = || {
+                if vertical {
+                    (bound, py + (cy - py) * (bound - px) / (cx - px))
+                } else {
+                    (px + (cx - px) * (bound - py) / (cy - py), bound)
+                }
+            };
+            match (inside(px, py, bound), inside(cx, cy, bound)) {
+                (true, true) => out.push((cx, cy)),
+                (true, false) => out.push(cross()),
+                (false, true) => {
+                    out.push(cross());
+                    out.push((cx, cy));
+                }
+                (false, false) => {}
+            }
+        }
+    }
+    out
+}
+
+/// Per-pixel polygon membership, row-major
+#[derive(Debug, Clone, PartialEq)]
+pub struct PolygonMask {
+    pub nrows: usize,
+    pub ncols: usize,
+    /// Pixel centre falls inside a polygon
+    pub inside: Vec<bool>,
+    /// Fraction (0-1) of the pixel area covered by the polygons
+    pub coverage: Vec<f64>,
+}
+
+impl ModisData {
+    /// Which pixels the polygons cover, and by how much
+    pub fn polygon_mask(&self, polygons: &[Polygon]) -> PolygonMask {
+        let nrows = self.nrows.max(0) as usize;
+        let ncols = self.ncols.max(0) as usize;
+        let projected: Vec<_> = polygons.iter().map(|p| p.to_sinusoidal()).collect();
+        let cell_area = self.cellsize * self.cellsize;
+
+        let mut inside = vec![false; nrows * ncols];
+        let mut coverage = vec![0.0; nrows * ncols];
+        for row in 0..nrows {
+            for col in 0..ncols {
+                let i = row * ncols + col;
+                let (xmin, ymin, xmax, ymax) = self.pixel_bounds(row, col);
+                let (cx, cy) = self.pixel_center(row, col);
+                let mut area = 0.0;
+                for (exterior, holes) in &projected {
+                    if ring_contains(exterior, cx, cy)
+                        && !holes.iter().any(|h| ring_contains(h, cx, cy))
+                    {
+                        inside[i] = true;
+                    }
+                    area += ring_area(&clip_ring(exterior, xmin, ymin, xmax, ymax));
+                    for hole in holes {
+                        area -= ring_area(&clip_ring(hole, xmin, ymin, xmax, ymax));
+                    }
+                }
+                coverage[i] = (area / cell_area).clamp(0.0, 1.0);
+            }
+        }
+        PolygonMask {
+            nrows,
+            ncols,
+            inside,
+            coverage,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::otago;
+    use serde_json::json;
+
+    #[test]
+    fn test_polygon_mask() {
+        let data = otago();
+        // Polygon around the centre pixel, reaching 40% into its right neighbour
+        let (xmin, ymin, xmax, ymax) = data.pixel_bounds(2, 2);
+        let part = (xmax - xmin) * 0.4;
+        let corners = [
+            (xmin, ymin),
+            (xmax + part, ymin),
+            (xmax + part, ymax),
+            (xmin, ymax),
+            (xmin, ymin),
+        ];
+        let ring: Vec<Value> = corners
+            .iter()
+            .map(|(x, y)| {
+                let (lat, lon) = sinusoidal::to_lat_lon(*x, *y);
+                json!([lon, lat])
+            })
+            .collect();
+        let geojson = json!({
+            "type": "Feature",
+            "properties": {},
+            "geometry": {"type": "Polygon", "coordinates": [ring]}
+        });
+        let polygons = Polygon::from_geojson(&geojson).unwrap();
+        assert_eq!(polygons.len(), 1);
+
+        let mask = data.polygon_mask(&polygons);
+        assert!(mask.inside[12]);
+        assert!(!mask.inside[13]);
+        assert!((mask.coverage[12] - 1.0).abs() < 0.01);
+        assert!((mask.coverage[13] - 0.4).abs() < 0.01);
+        assert_eq!(mask.coverage[0], 0.0);
+    }
+
+    #[test]
+    fn test_clip() {
+        let square = [(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0)];
+        assert_eq!(ring_area(&clip_ring(&square, 1.0, 1.0, 3.0, 3.0)), 1.0);
+        assert!(ring_contains(&square, 1.0, 1.0));
+        assert!(!ring_contains(&square, 3.0, 1.0));
+    }
+
+    #[test]
+    fn test_wkt() {
+        let polygons = Polygon::from_wkt(
+            "MULTIPOLYGON (((170 -45, 171 -45, 171 -46, 170 -45)), \
+             ((172 -44,173 -44,173 -45,172 -44),(172.5 -44.2, 172.8 -44.2, 172.8 -44.4, 172.5 -44.2)))",
+        )
+        .unwrap();
+        assert_eq!(polygons.len(), 2);
+        assert_eq!(polygons[0].exterior[1], (171.0, -45.0));
+        assert_eq!(polygons[1].holes.len(), 1);
+        let polygon = Polygon::from_wkt("polygon((0 0, 1 0, 1 1, 0 0))").unwrap();
+        assert_eq!(polygon[0].exterior.len(), 4);
+        assert!(Polygon::from_wkt("POLYGON EMPTY").unwrap().is_empty());
+        assert!(Polygon::from_wkt("POINT (1 2)").is_err());
+        assert!(Polygon::from_wkt("POLYGON ((0 0, 1 x))").is_err());
+    }
+}
@@ -0,0 +1,240 @@
+// Subsets for bounding boxes and polygons
+//
+// The subset endpoint takes a centre point and up to 100 km above/below and left/right.
+// Larger areas are split into several requests, which are mosaicked and cropped back to
+// the area's sinusoidal extent.
+
+use serde_json::Value;
+
+use crate::mosaic::{mosaic, Overlap};
+use crate::polygon::{Polygon, PolygonMask};
+use crate::sinusoidal;
+use crate::structs::{ModisData, Subset};
+
+/// Largest kmAboveBelow/kmLeftRight the API accepts
+pub const MAX_KM: u8 = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Request {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub km_above_below: u8,
+    pub km_left_right: u8,
+}
+
+/// Sinusoidal (xmin, ymin, xmax, ymax) of a lat/lon box, its edges are sampled since
+/// meridians curve in the sinusoidal projection
+pub fn sinusoidal_extent(
+    min_lat: f64,
+    min_lon: f64,
+    max_lat: f64,
+    max_lon: f64,
+) -> (f64, f64, f64, f64) {
+    let steps = 32;
+    let mut extent = (
+        f64::INFINITY,
+        f64::INFINITY,
+        f64::NEG_INFINITY,
+        f64::NEG_INFINITY,
+    );
+    for s in 0..=steps {
+        let t = s as f64 / steps as f64;
+        let lat = min_lat + (max_lat - min_lat) * t;
+        let lon = min_lon + (max_lon - min_lon) * t;
+        for (lat, lon) in [
+            (lat, min_lon),
+            (lat, max_lon),
+            (min_lat, lon),
+            (max_lat, lon),
+        ] {
+            let (x, y) = sinusoidal::to_sinusoidal(lat, lon);
+            extent = (
+                extent.0.min(x),
+                extent.1.min(y),
+                extent.2.max(x),
+                extent.3.max(y),
+            );
+        }
+    }
+    extent
+}
+
+/// Smallest set of requests covering a lat/lon box, one unless it is over ~200 km across
+pub fn plan_requests(min_lat: f64, min_lon: f64, max_lat: f64, max_lon: f64) -> Vec<Request> {
+    let (xmin, ymin, xmax, ymax) = sinusoidal_extent(min_lat, min_lon, max_lat, max_lon);
+    // A 1 km margin covers the centre pixel offset for every MODIS cell size
+    let max_half = (MAX_KM as f64 - 1.0) * 1000.0;
+    let tiles = |size: f64| ((size / (2.0 * max_half)).ceil() as usize).max(1);
+    let (nx, ny) = (tiles(xmax - xmin), tiles(ymax - ymin));
+    let (width, height) = ((xmax - xmin) / nx as f64, (ymax - ymin) / ny as f64);
+    let km = |size: f64| ((size / 2.0 / 1000.0 + 1.0).ceil() as u8).min(MAX_KM);
+
+    let mut requests = Vec::new();
+    for j in 0..ny {
+        for i in 0..nx {
+            let x = xmin + (i as f64 + 0.5) * width;
+            let y = ymin + (j as f64 + 0.5) * height;
+            let (latitude, longitude) = sinusoidal::to_lat_lon(x, y);
+            requests.push(Request {
+                latitude,
+                longitude,
+                km_above_below: km(height),
+                km_left_right: km(width),
+            });
+        }
+    }
+    requests
+}
+
+impl ModisData {
+    /// Rows `row..row + nrows` and cols `col..col + ncols` as a new ModisData
+    pub fn crop(&self, row: usize, col: usize, nrows: usize, ncols: usize) -> ModisData {
+        let in_cols = self.ncols.max(0) as usize;
+        let nrows = nrows.min((self.nrows.max(0) as usize).saturating_sub(row));
+        let ncols = ncols.min(in_cols.saturating_sub(col));
+        let (xmin, ymin, _, _) = self.pixel_bounds(row + nrows.max(1) - 1, col);
+        ModisData {
+            xllcorner: format!("{:.2}", xmin),
+            yllcorner: format!("{:.2}", ymin),
+            nrows: nrows as i32,
+            ncols: ncols as i32,
+            subset: self
+                .subset
+                .iter()
+                .map(|s| Subset {
+                    data: (row..row + nrows)
+                        .flat_map(|r| (col..col + ncols).map(move |c| r * in_cols + c))
+                        .filter_map(|i| s.data.get(i).copied())
+                        .collect(),
+                    ..s.clone()
+                })
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    /// Pixels intersecting a sinusoidal extent, None if it misses the grid
+    pub fn crop_to_extent(&self, xmin: f64, ymin: f64, xmax: f64, ymax: f64) -> Option<ModisData> {
+        let top = self.yll() + self.nrows as f64 * self.cellsize;
+        let col0 = ((xmin - self.xll()) / self.cellsize).floor().max(0.0) as usize;
+        let col1 = ((xmax - self.xll()) / self.cellsize)
+            .ceil()
+            .min(self.ncols as f64);
+        let row0 = ((top - ymax) / self.cellsize).floor().max(0.0) as usize;
+        let row1 = ((top - ymin) / self.cellsize).ceil().min(self.nrows as f64);
+        if col1 <= col0 as f64 || row1 <= row0 as f64 {
+            return None;
+        }
+        Some(self.crop(row0, col0, row1 as usize - row0, col1 as usize - col0))
+    }
+}
+
+/// Subset covering a lat/lon box, split into several requests when needed
+///
+/// The result covers the box's sinusoidal bounding rectangle, `latitude`/`longitude` are
+/// set to the box centre.
+#[allow(clippy::too_many_arguments)]
+pub async fn subset_bbox(
+    product: &str,
+    min_lat: f64,
+    min_lon: f64,
+    max_lat: f64,
+    max_lon: f64,
+    band: &str,
+    start_date: &str,
+    end_date: &str,
+) -> Result<ModisData, Box<dyn std::error::Error>> {
+    if min_lat >= max_lat || min_lon >= max_lon {
+        return Err("empty bounding box".into());
+    }
+    let mut parts = Vec::new();
+    for request in plan_requests(min_lat, min_lon, max_lat, max_lon) {
+        parts.push(
+            crate::subset(
+                product,
+                request.latitude,
+                request.longitude,
+                band,
+                start_date,
+                end_date,
+                request.km_above_below,
+                request.km_left_right,
+            )
+            .await?,
+        );
+    }
+    let (xmin, ymin, xmax, ymax) = sinusoidal_extent(min_lat, min_lon, max_lat, max_lon);
+    let mut data = mosaic(&parts, Overlap::Priority)?
+        .crop_to_extent(xmin, ymin, xmax, ymax)
+        .ok_or("requests did not cover the bounding box")?;
+    data.latitude = (min_lat + max_lat) / 2.0;
+    data.longitude = (min_lon + max_lon) / 2.0;
+    Ok(data)
+}
+
+/// Subset covering GeoJSON polygons (see [`Polygon::from_geojson`]) with the per-pixel
+/// inclusion and coverage mask
+pub async fn subset_polygon(
+    product: &str,
+    geojson: &Value,
+    band: &str,
+    start_date: &str,
+    end_date: &str,
+) -> Result<(ModisData, PolygonMask), Box<dyn std::error::Error>> {
+    let polygons = Polygon::from_geojson(geojson)?;
+    let (min_lon, min_lat, max_lon, max_lat) =
+        Polygon::bbox(&polygons).ok_or("no polygons in the GeoJSON")?;
+    let data = subset_bbox(
+        product, min_lat, min_lon, max_lat, max_lon, band, start_date, end_date,
+    )
+    .await?;
+    let mask = data.polygon_mask(&polygons);
+    Ok((data, mask))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::otago;
+
+    #[test]
+    fn test_plan_requests() {
+        // A few km across Otago, one request
+        let small = plan_requests(-45.9, 170.6, -45.85, 170.7);
+        assert_eq!(small.len(), 1);
+        assert!((small[0].latitude + 45.875).abs() < 0.01);
+        assert!(small[0].km_above_below <= 5);
+
+        // Roughly 330 km east-west and 110 km north-south on the equator, where meridians
+        // are not sheared
+        let large = plan_requests(-0.5, 0.0, 0.5, 3.0);
+        assert_eq!(large.len(), 2);
+        assert!(large
+            .iter()
+            .all(|r| r.km_left_right <= MAX_KM && r.km_above_below <= MAX_KM));
+        assert!(large[0].longitude < large[1].longitude);
+    }
+
+    #[test]
+    fn test_crop_to_extent() {
+        let data = otago();
+        let (xmin, ymin, _, _) = data.pixel_bounds(2, 1);
+        let (_, _, xmax, ymax) = data.pixel_bounds(1, 2);
+        let crop = data.crop_to_extent(xmin + 1.0, ymin + 1.0, xmax - 1.0, ymax - 1.0);
+        let crop = crop.unwrap();
+        assert_eq!((crop.nrows, crop.ncols), (2, 2));
+        assert_eq!(crop.subset[0].data, vec![9, 4, 1, 254]);
+        assert!(data.crop_to_extent(0.0, 0.0, 1.0, 1.0).is_none());
+    }
+
+    #[test]
+    fn test_crop() {
+        let data = otago();
+        let crop = data.crop(1, 2, 2, 10);
+        assert_eq!((crop.nrows, crop.ncols), (2, 3));
+        assert_eq!(crop.subset[0].data, vec![4, 254, 254, 254, 3, 9]);
+        let (x, y) = crop.pixel_center(0, 0);
+        let (ex, ey) = data.pixel_center(1, 2);
+        assert!((x - ex).abs() < 0.01 && (y - ey).abs() < 0.01);
+    }
+}
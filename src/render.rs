@@ -0,0 +1,451 @@
+// Quicklook rendering of a Subset to an RGBA image, with optional legend, and PNG output
+
+use std::io::Write;
+use std::path::Path;
+
+use crate::landcover::Scheme;
+use crate::mask::Mask;
+use crate::stats::percentile;
+use crate::structs::{ModisData, Subset};
+
+pub type Rgba = [u8; 4];
+
+pub const TRANSPARENT: Rgba = [0, 0, 0, 0];
+pub const WHITE: Rgba = [255, 255, 255, 255];
+pub const BLACK: Rgba = [0, 0, 0, 255];
+
+// Colour ramps as evenly spaced stops
+const VIRIDIS: [[u8; 3]; 11] = [
+    [68, 1, 84],
+    [72, 36, 117],
+    [65, 68, 135],
+    [53, 95, 141],
+    [42, 120, 142],
+    [33, 145, 140],
+    [34, 168, 132],
+    [68, 191, 112],
+    [122, 209, 81],
+    [189, 223, 38],
+    [253, 231, 37],
+];
+
+const NDVI: [[u8; 3]; 5] = [
+    [120, 70, 20],
+    [200, 160, 90],
+    [240, 230, 140],
+    [100, 180, 60],
+    [0, 90, 30],
+];
+
+const LST: [[u8; 3]; 5] = [
+    [49, 54, 149],
+    [116, 173, 209],
+    [255, 255, 191],
+    [244, 109, 67],
+    [165, 0, 38],
+];
+
+const GRAY: [[u8; 3]; 2] = [[0, 0, 0], [255, 255, 255]];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Colormap {
+    Viridis,
+    Ndvi,
+    Lst,
+    Gray,
+    /// Raw class value to colour, for land cover and other class products
+    Categorical(Vec<(i32, Rgba)>),
+}
+
+impl Colormap {
+    /// "viridis", "ndvi", "lst", "gray" or a land cover scheme name, see [`Scheme::from_name`]
+    pub fn from_name(name: &str) -> Option<Colormap> {
+        match name.to_lowercase().as_str() {
+            "viridis" => Some(Colormap::Viridis),
+            "ndvi" => Some(Colormap::Ndvi),
+            "lst" => Some(Colormap::Lst),
+            "gray" | "grey" => Some(Colormap::Gray),
+            _ => Scheme::from_name(name).map(|s| s.colormap()),
+        }
+    }
+
+    /// IGBP (LC_Type1) class colours
+    pub fn igbp() -> Colormap {
+        Scheme::Igbp.colormap()
+    }
+
+    pub fn is_categorical(&self) -> bool {
+        matches!(self, Colormap::Categorical(_))
+    }
+
+    /// Colour at `t` in 0..1 for continuous colormaps, the first class for categorical ones
+    pub fn color(&self, t: f64) -> Rgba {
+        let stops: &[[u8; 3]] = match self {
+            Colormap::Viridis => &VIRIDIS,
+            Colormap::Ndvi => &NDVI,
+            Colormap::Lst => &LST,
+            Colormap::Gray => &GRAY,
+            Colormap::Categorical(classes) => {
+                return classes.first().map(|c| c.1).unwrap_or(TRANSPARENT)
+            }
+        };
+        let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+        let pos = t * (stops.len() - 1) as f64;
+        let i = (pos.floor() as usize).min(stops.len() - 2);
+        let f = pos - i as f64;
+        let mut out = [0, 0, 0, 255];
+        for c in 0..3 {
+            out[c] = (stops[i][c] as f64 + (stops[i + 1][c] as f64 - stops[i][c] as f64) * f)
+                .round() as u8;
+        }
+        out
+    }
+
+    /// Colour of a raw class value, None if the class isn't in the palette
+    pub fn class_color(&self, value: i32) -> Option<Rgba> {
+        match self {
+            Colormap::Categorical(classes) => classes
+                .iter()
+                .find(|(c, _)| *c == value)
+                .map(|(_, rgba)| *rgba),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stretch {
+    /// Fixed range in scaled units
+    Fixed(f64, f64),
+    /// Percentiles (0-100) of the valid scaled values
+    Percentile(f64, f64),
+    MinMax,
+}
+
+impl Stretch {
+    /// Resolve to a (min, max) range for the given scaled values
+    pub fn range(&self, values: &[f64]) -> (f64, f64) {
+        if let Stretch::Fixed(min, max) = self {
+            return (*min, *max);
+        }
+        let mut sorted: Vec<f64> = values.iter().copied().filter(|v| v.is_finite()).collect();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        match self {
+            Stretch::Percentile(lo, hi) => (percentile(&sorted, *lo), percentile(&sorted, *hi)),
+            _ => (
+                sorted.first().copied().unwrap_or(f64::NAN),
+                sorted.last().copied().unwrap_or(f64::NAN),
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    pub colormap: Colormap,
+    pub stretch: Stretch,
+    /// None uses the built in mask for the band, see [`ModisData::mask`]
+    pub mask: Option<Mask>,
+    pub fill_color: Rgba,
+    /// Output pixels per data pixel
+    pub upscale: u32,
+    pub legend: bool,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            colormap: Colormap::Viridis,
+            stretch: Stretch::Percentile(2.0, 98.0),
+            mask: None,
+            fill_color: TRANSPARENT,
+            upscale: 1,
+            legend: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RgbaImage {
+    pub width: u32,
+    pub height: u32,
+    /// Row-major RGBA, 4 bytes per pixel
+    pub pixels: Vec<u8>,
+}
+
+// 3x5 bitmap glyphs for labels, each row is the low 3 bits
+fn glyph(c: char) -> [u8; 5] {
+    match c {
+        '0' => [7, 5, 5, 5, 7],
+        '1' => [2, 6, 2, 2, 7],
+        '2' => [7, 1, 7, 4, 7],
+        '3' => [7, 1, 7, 1, 7],
+        '4' => [5, 5, 7, 1, 1],
+        '5' => [7, 4, 7, 1, 7],
+        '6' => [7, 4, 7, 5, 7],
+        '7' => [7, 1, 1, 1, 1],
+        '8' => [7, 5, 7, 5, 7],
+        '9' => [7, 5, 7, 1, 7],
+        '-' => [0, 0, 7, 0, 0],
+        '.' => [0, 0, 0, 0, 2],
+        ':' => [0, 2, 0, 2, 0],
+        '/' => [1, 1, 2, 4, 4],
+        'A' => [2, 5, 7, 5, 5],
+        _ => [0; 5],
+    }
+}
+
+impl RgbaImage {
+    pub fn new(width: u32, height: u32, color: Rgba) -> RgbaImage {
+        let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+        for _ in 0..width * height {
+            pixels.extend(color);
+        }
+        RgbaImage {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> Rgba {
+        let i = ((y * self.width + x) * 4) as usize;
+        [
+            self.pixels[i],
+            self.pixels[i + 1],
+            self.pixels[i + 2],
+            self.pixels[i + 3],
+        ]
+    }
+
+    /// Set a pixel, out of bounds is ignored
+    pub fn put(&mut self, x: u32, y: u32, color: Rgba) {
+        if x < self.width && y < self.height {
+            let i = ((y * self.width + x) * 4) as usize;
+            self.pixels[i..i + 4].copy_from_slice(&color);
+        }
+    }
+
+    pub fn fill_rect(&mut self, x: u32, y: u32, width: u32, height: u32, color: Rgba) {
+        for yy in y..y + height {
+            for xx in x..x + width {
+                self.put(xx, yy, color);
+            }
+        }
+    }
+
+    /// Width in pixels of `text` drawn with [`RgbaImage::draw_text`]
+    pub fn text_width(text: &str, scale: u32) -> u32 {
+        (text.chars().count() as u32 * 4).saturating_sub(1) * scale
+    }
+
+    /// Draw text with the built in 3x5 font (digits, '-', '.', ':', '/' and 'A')
+    pub fn draw_text(&mut self, x: u32, y: u32, text: &str, scale: u32, color: Rgba) {
+        for (i, c) in text.chars().enumerate() {
+            let rows = glyph(c);
+            for (gy, bits) in rows.iter().enumerate() {
+                for gx in 0..3 {
+                    if bits & (4 >> gx) != 0 {
+                        self.fill_rect(
+                            x + (i as u32 * 4 + gx) * scale,
+                            y + gy as u32 * scale,
+                            scale,
+                            scale,
+                            color,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// Copy `other` into this image at (x, y)
+    pub fn blit(&mut self, other: &RgbaImage, x: u32, y: u32) {
+        for yy in 0..other.height {
+            for xx in 0..other.width {
+                self.put(x + xx, y + yy, other.get(xx, yy));
+            }
+        }
+    }
+
+    pub fn write_png<W: Write>(&self, writer: W) -> Result<(), Box<dyn std::error::Error>> {
+        let mut encoder = png::Encoder::new(writer, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+        Ok(())
+    }
+
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn std::error::Error>> {
+        let file = std::fs::File::create(path)?;
+        self.write_png(std::io::BufWriter::new(file))
+    }
+}
+
+fn label(value: f64) -> String {
+    if value.abs() >= 100.0 {
+        format!("{:.0}", value)
+    } else if value.abs() >= 1.0 {
+        format!("{:.1}", value)
+    } else {
+        format!("{:.2}", value)
+    }
+}
+
+/// Valid scaled values of a subset, used to resolve the stretch
+pub fn valid_values(data: &ModisData, subset: &Subset, mask: &Mask) -> Vec<f64> {
+    subset
+        .data
+        .iter()
+        .filter_map(|v| data.masked_value(*v, mask))
+        .collect()
+}
+
+/// Render with an already resolved (min, max) range, so several dates can share a stretch
+pub fn render_with_range(
+    data: &ModisData,
+    subset: &Subset,
+    options: &RenderOptions,
+    range: (f64, f64),
+) -> RgbaImage {
+    let mask = options.mask.clone().unwrap_or_else(|| data.mask());
+    let ncols = data.ncols.max(0) as u32;
+    let nrows = data.nrows.max(0) as u32;
+    let upscale = options.upscale.max(1);
+    let (min, max) = range;
+
+    let mut map = RgbaImage::new(ncols * upscale, nrows * upscale, options.fill_color);
+    for (i, value) in subset
+        .data
+        .iter()
+        .enumerate()
+        .take((ncols * nrows) as usize)
+    {
+        let color = match data.masked_value(*value, &mask) {
+            None => options.fill_color,
+            Some(_) if options.colormap.is_categorical() => options
+                .colormap
+                .class_color(*value)
+                .unwrap_or(options.fill_color),
+            Some(scaled) => {
+                let t = if max > min {
+                    (scaled - min) / (max - min)
+                } else {
+                    0.5
+                };
+                options.colormap.color(t)
+            }
+        };
+        let (col, row) = (i as u32 % ncols, i as u32 / ncols);
+        map.fill_rect(col * upscale, row * upscale, upscale, upscale, color);
+    }
+
+    if !options.legend {
+        return map;
+    }
+
+    // Legend panel to the right of the map
+    let fs = (map.height / 80).clamp(1, 4);
+    let pad = 3 * fs;
+    let line = 7 * fs;
+
+    let legend = match &options.colormap {
+        Colormap::Categorical(classes) => {
+            let present: Vec<&(i32, Rgba)> = classes
+                .iter()
+                .filter(|(c, _)| subset.data.contains(c))
+                .collect();
+            let label_w = present
+                .iter()
+                .map(|(c, _)| RgbaImage::text_width(&c.to_string(), fs))
+                .max()
+                .unwrap_or(0);
+            let mut legend = RgbaImage::new(
+                pad * 3 + 5 * fs + label_w,
+                (pad * 2 + line * present.len() as u32).max(map.height),
+                WHITE,
+            );
+            for (i, (class, color)) in present.iter().enumerate() {
+                let y = pad + i as u32 * line;
+                legend.fill_rect(pad, y, 5 * fs, 5 * fs, *color);
+                legend.draw_text(pad * 2 + 5 * fs, y, &class.to_string(), fs, BLACK);
+            }
+            legend
+        }
+        _ => {
+            let labels = [label(max), label((min + max) / 2.0), label(min)];
+            let label_w = labels
+                .iter()
+                .map(|l| RgbaImage::text_width(l, fs))
+                .max()
+                .unwrap_or(0);
+            let bar_w = 6 * fs;
+            let height = map.height.max(pad * 2 + line * 3);
+            let mut legend = RgbaImage::new(pad * 3 + bar_w + label_w, height, WHITE);
+            let bar_h = height - pad * 2;
+            for y in 0..bar_h {
+                let t = 1.0 - y as f64 / (bar_h.max(2) - 1) as f64;
+                legend.fill_rect(pad, pad + y, bar_w, 1, options.colormap.color(t));
+            }
+            let x = pad * 2 + bar_w;
+            legend.draw_text(x, pad, &labels[0], fs, BLACK);
+            legend.draw_text(x, pad + bar_h / 2 - (5 * fs) / 2, &labels[1], fs, BLACK);
+            legend.draw_text(x, pad + bar_h - 5 * fs, &labels[2], fs, BLACK);
+            legend
+        }
+    };
+
+    let mut out = RgbaImage::new(
+        map.width + legend.width,
+        map.height.max(legend.height),
+        TRANSPARENT,
+    );
+    out.blit(&map, 0, 0);
+    out.blit(&legend, map.width, 0);
+    out
+}
+
+/// Render a subset of `data` to an RGBA image
+pub fn render(data: &ModisData, subset: &Subset, options: &RenderOptions) -> RgbaImage {
+    let mask = options.mask.clone().unwrap_or_else(|| data.mask());
+    let range = options.stretch.range(&valid_values(data, subset, &mask));
+    render_with_range(data, subset, options, range)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::otago;
+
+    #[test]
+    fn test_render() {
+        let data = otago();
+        let options = RenderOptions {
+            stretch: Stretch::MinMax,
+            upscale: 4,
+            ..Default::default()
+        };
+        let image = render(&data, &data.subset[0], &options);
+        assert_eq!((image.width, image.height), (20, 20));
+        // 254 is outside the LAI valid range so it is transparent
+        assert_eq!(image.get(3 * 4, 4), TRANSPARENT);
+        // 18 is the maximum, 1 the minimum
+        assert_eq!(image.get(2 * 4, 4 * 4), Colormap::Viridis.color(1.0));
+        assert_eq!(image.get(4, 2 * 4), Colormap::Viridis.color(0.0));
+
+        let with_legend = render(
+            &data,
+            &data.subset[0],
+            &RenderOptions {
+                legend: true,
+                ..options
+            },
+        );
+        assert!(with_legend.width > image.width);
+
+        let mut png = Vec::new();
+        with_legend.write_png(&mut png).unwrap();
+        assert_eq!(&png[1..4], b"PNG");
+    }
+}
@@ -0,0 +1,359 @@
+// Resample subsets from the sinusoidal grid to regular geographic or UTM grids
+//
+// Every target cell centre is projected back to sinusoidal x/y and sampled from the
+// source grid, so no intermediate rasters or external projection libraries are needed.
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::mask::Mask;
+use crate::sinusoidal;
+use crate::structs::ModisData;
+use crate::utm;
+
+// Most samples per cell axis for mode resampling
+const MAX_SAMPLES: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Crs {
+    /// EPSG:4326, x is longitude and y latitude in degrees
+    Wgs84,
+    /// EPSG:326xx (north) or 327xx (south), metres
+    Utm { zone: u8, north: bool },
+}
+
+impl Crs {
+    pub fn from_epsg(code: u32) -> Option<Crs> {
+        match code {
+            4326 => Some(Crs::Wgs84),
+            32601..=32660 => Some(Crs::Utm {
+                zone: (code - 32600) as u8,
+                north: true,
+            }),
+            32701..=32760 => Some(Crs::Utm {
+                zone: (code - 32700) as u8,
+                north: false,
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn epsg(&self) -> u32 {
+        match self {
+            Crs::Wgs84 => 4326,
+            Crs::Utm { zone, north: true } => 32600 + *zone as u32,
+            Crs::Utm { zone, north: false } => 32700 + *zone as u32,
+        }
+    }
+
+    /// UTM zone containing a point
+    pub fn utm_for(latitude: f64, longitude: f64) -> Crs {
+        Crs::Utm {
+            zone: utm::zone(longitude),
+            north: latitude >= 0.0,
+        }
+    }
+
+    /// Latitude/longitude (degrees) to x/y in this CRS
+    pub fn forward(&self, latitude: f64, longitude: f64) -> (f64, f64) {
+        match self {
+            Crs::Wgs84 => (longitude, latitude),
+            Crs::Utm { zone, north } => utm::to_utm(latitude, longitude, *zone, *north),
+        }
+    }
+
+    /// x/y in this CRS to (latitude, longitude)
+    pub fn inverse(&self, x: f64, y: f64) -> (f64, f64) {
+        match self {
+            Crs::Wgs84 => (y, x),
+            Crs::Utm { zone, north } => utm::from_utm(x, y, *zone, *north),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resampling {
+    Nearest,
+    /// Weighted by distance to the four nearest pixel centres, masked pixels are left out
+    Bilinear,
+    /// Most common valid value of the source pixels within the cell, for categorical bands
+    Mode,
+}
+
+/// Regular north-up target grid, row 0 is the top row
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct TargetGrid {
+    pub crs: Crs,
+    /// Left edge
+    pub xmin: f64,
+    /// Top edge
+    pub ymax: f64,
+    /// Cell size in CRS units (degrees for WGS84)
+    pub resolution: f64,
+    pub nrows: usize,
+    pub ncols: usize,
+}
+
+impl TargetGrid {
+    /// Grid in `crs` covering every pixel of `data`, with edges on multiples of `resolution`
+    pub fn covering(data: &ModisData, crs: Crs, resolution: f64) -> TargetGrid {
+        let (nrows, ncols) = (data.nrows.max(0) as usize, data.ncols.max(0) as usize);
+        let top = data.yll() + nrows as f64 * data.cellsize;
+        let right = data.xll() + ncols as f64 * data.cellsize;
+        let steps = 4 * nrows.max(ncols).max(1);
+
+        // The outline of the source grid is curved in the target CRS, so it is sampled
+        let (mut xmin, mut ymin) = (f64::INFINITY, f64::INFINITY);
+        let (mut xmax, mut ymax) = (f64::NEG_INFINITY, f64::NEG_INFINITY);
+        for s in 0..=steps {
+            let t = s as f64 / steps as f64;
+            let x = data.xll() + (right - data.xll()) * t;
+            let y = data.yll() + (top - data.yll()) * t;
+            for (sx, sy) in [(x, data.yll()), (x, top), (data.xll(), y), (right, y)] {
+                let (lat, lon) = sinusoidal::to_lat_lon(sx, sy);
+                let (px, py) = crs.forward(lat, lon);
+                xmin = xmin.min(px);
+                ymin = ymin.min(py);
+                xmax = xmax.max(px);
+                ymax = ymax.max(py);
+            }
+        }
+        let xmin = (xmin / resolution).floor() * resolution;
+        let ymax = (ymax / resolution).ceil() * resolution;
+        TargetGrid {
+            crs,
+            xmin,
+            ymax,
+            resolution,
+            nrows: ((ymax - ymin) / resolution).ceil().max(1.0) as usize,
+            ncols: ((xmax - xmin) / resolution).ceil().max(1.0) as usize,
+        }
+    }
+
+    /// x/y of the centre of the cell at (row, col)
+    pub fn cell_center(&self, row: usize, col: usize) -> (f64, f64) {
+        (
+            self.xmin + (col as f64 + 0.5) * self.resolution,
+            self.ymax - (row as f64 + 0.5) * self.resolution,
+        )
+    }
+
+    /// Latitude/longitude of the centre of the cell at (row, col)
+    pub fn cell_lat_lon(&self, row: usize, col: usize) -> (f64, f64) {
+        let (x, y) = self.cell_center(row, col);
+        self.crs.inverse(x, y)
+    }
+}
+
+/// One date on the target grid
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Layer {
+    pub modis_date: String,
+    pub calendar_date: String,
+    /// Scaled values, row-major, None for masked pixels and cells outside the subset
+    pub values: Vec<Option<f64>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Reprojected {
+    pub grid: TargetGrid,
+    pub band: String,
+    pub units: String,
+    pub layers: Vec<Layer>,
+}
+
+// Fractional (row, col) of a sinusoidal point in pixel units, pixel centres are whole
+fn source_position(data: &ModisData, x: f64, y: f64) -> (f64, f64) {
+    let top = data.yll() + data.nrows as f64 * data.cellsize;
+    (
+        (top - y) / data.cellsize - 0.5,
+        (x - data.xll()) / data.cellsize - 0.5,
+    )
+}
+
+fn bilinear(data: &ModisData, mask: &Mask, values: &[i32], x: f64, y: f64) -> Option<f64> {
+    data.pixel_at_xy(x, y)?;
+    let (nrows, ncols) = (data.nrows as usize, data.ncols as usize);
+    let (r, c) = source_position(data, x, y);
+    let (r0, c0) = (r.floor(), c.floor());
+    let (fr, fc) = (r - r0, c - c0);
+
+    let (mut sum, mut weights) = (0.0, 0.0);
+    for (dr, wr) in [(0.0, 1.0 - fr), (1.0, fr)] {
+        for (dc, wc) in [(0.0, 1.0 - fc), (1.0, fc)] {
+            let (row, col) = (r0 + dr, c0 + dc);
+            if row < 0.0 || col < 0.0 || row >= nrows as f64 || col >= ncols as f64 {
+                continue;
+            }
+            let weight = wr * wc;
+            if let Some(v) = values
+                .get(row as usize * ncols + col as usize)
+                .and_then(|v| data.masked_value(*v, mask))
+            {
+                sum += v * weight;
+                weights += weight;
+            }
+        }
+    }
+    (weights > 0.0).then(|| sum / weights)
+}
+
+impl ModisData {
+    /// Resample every date onto `grid`
+    pub fn reproject(&self, grid: &TargetGrid, resampling: Resampling) -> Reprojected {
+        let mask = self.mask();
+        let ncols = self.ncols.max(0) as usize;
+        let half = grid.resolution / 2.0;
+
+        // Source pixel indices sampled by each target cell, shared by every date
+        let samples: Vec<Vec<usize>> = (0..grid.nrows * grid.ncols)
+            .map(|i| {
+                let (row, col) = (i / grid.ncols, i % grid.ncols);
+                let (cx, cy) = grid.cell_center(row, col);
+                let n = match resampling {
+                    Resampling::Mode => {
+                        // Cell size in sinusoidal metres, from two opposite corners
+                        let (lat0, lon0) = grid.crs.inverse(cx - half, cy - half);
+                        let (lat1, lon1) = grid.crs.inverse(cx + half, cy + half);
+                        let (x0, y0) = sinusoidal::to_sinusoidal(lat0, lon0);
+                        let (x1, y1) = sinusoidal::to_sinusoidal(lat1, lon1);
+                        let size = (x1 - x0).abs().max((y1 - y0).abs());
+                        ((size / self.cellsize).ceil() as usize).clamp(1, MAX_SAMPLES)
+                    }
+                    _ => 1,
+                };
+                let step = grid.resolution / n as f64;
+                let mut pixels = Vec::with_capacity(n * n);
+                for sr in 0..n {
+                    for sc in 0..n {
+                        let px = cx - half + (sc as f64 + 0.5) * step;
+                        let py = cy + half - (sr as f64 + 0.5) * step;
+                        let (lat, lon) = grid.crs.inverse(px, py);
+                        let (x, y) = sinusoidal::to_sinusoidal(lat, lon);
+                        if let Some((r, c)) = self.pixel_at_xy(x, y) {
+                            pixels.push(r * ncols + c);
+                        }
+                    }
+                }
+                pixels
+            })
+            .collect();
+
+        let layers = self
+            .subset
+            .iter()
+            .map(|subset| {
+                let values = samples
+                    .iter()
+                    .enumerate()
+                    .map(|(i, pixels)| match resampling {
+                        Resampling::Nearest => pixels
+                            .first()
+                            .and_then(|p| subset.data.get(*p))
+                            .and_then(|v| self.masked_value(*v, &mask)),
+                        Resampling::Bilinear => {
+                            let (cx, cy) = grid.cell_center(i / grid.ncols, i % grid.ncols);
+                            let (lat, lon) = grid.crs.inverse(cx, cy);
+                            let (x, y) = sinusoidal::to_sinusoidal(lat, lon);
+                            bilinear(self, &mask, &subset.data, x, y)
+                        }
+                        Resampling::Mode => {
+                            let mut counts: BTreeMap<i32, usize> = BTreeMap::new();
+                            for v in pixels.iter().filter_map(|p| subset.data.get(*p)) {
+                                if mask.is_valid(*v) {
+                                    *counts.entry(*v).or_default() += 1;
+                                }
+                            }
+                            // Ties go to the smallest value
+                            let mode = counts
+                                .into_iter()
+                                .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)));
+                            mode.map(|(v, _)| self.scale_value(v))
+                        }
+                    })
+                    .collect();
+                Layer {
+                    modis_date: subset.modis_date.clone(),
+                    calendar_date: subset.calendar_date.clone(),
+                    values,
+                }
+            })
+            .collect();
+
+        Reprojected {
+            grid: *grid,
+            band: self.band.clone(),
+            units: self.units.clone(),
+            layers,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::otago;
+
+    #[test]
+    fn test_crs() {
+        assert_eq!(
+            Crs::from_epsg(32759),
+            Some(Crs::Utm {
+                zone: 59,
+                north: false
+            })
+        );
+        assert_eq!(Crs::utm_for(-45.87, 170.67).epsg(), 32759);
+        assert_eq!(Crs::from_epsg(3857), None);
+    }
+
+    #[test]
+    fn test_reproject() {
+        let data = otago();
+        let grid = TargetGrid::covering(&data, Crs::Wgs84, 0.001);
+        let (lat, lon) = data.pixel_lat_lon(0, 0);
+        assert!(lon > grid.xmin && lat < grid.ymax);
+
+        let nearest = data.reproject(&grid, Resampling::Nearest);
+        assert_eq!(nearest.layers.len(), 1);
+        let values = &nearest.layers[0].values;
+        assert_eq!(values.len(), grid.nrows * grid.ncols);
+        // A cell over the top left pixel, 13 scaled by 0.1
+        let col = ((lon - grid.xmin) / grid.resolution) as usize;
+        let row = ((grid.ymax - lat) / grid.resolution) as usize;
+        assert!((values[row * grid.ncols + col].unwrap() - 1.3).abs() < 1e-9);
+        // Corners of the covering grid fall outside the sheared subset
+        assert!(values.iter().any(|v| v.is_none()));
+
+        // Bilinear at a pixel centre is that pixel's value
+        let grid = TargetGrid {
+            crs: Crs::Wgs84,
+            xmin: lon - 0.0005,
+            ymax: lat + 0.0005,
+            resolution: 0.001,
+            nrows: 1,
+            ncols: 1,
+        };
+        let bilinear = data.reproject(&grid, Resampling::Bilinear);
+        assert!((bilinear.layers[0].values[0].unwrap() - 1.3).abs() < 1e-6);
+
+        // One UTM cell over the whole subset takes its most common valid value
+        let mut classes = data.clone();
+        classes.subset[0].data = vec![7; 25];
+        classes.subset[0].data[..6].copy_from_slice(&[3, 3, 3, 254, 254, 254]);
+        let (lat, lon) = data.pixel_lat_lon(2, 2);
+        let crs = Crs::utm_for(lat, lon);
+        let (x, y) = crs.forward(lat, lon);
+        let grid = TargetGrid {
+            crs,
+            xmin: x - 2500.0,
+            ymax: y + 2500.0,
+            resolution: 5000.0,
+            nrows: 1,
+            ncols: 1,
+        };
+        let mode = classes.reproject(&grid, Resampling::Mode);
+        assert!((mode.layers[0].values[0].unwrap() - 0.7).abs() < 1e-9);
+    }
+}
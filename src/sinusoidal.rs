@@ -0,0 +1,45 @@
+// MODIS sinusoidal projection (SR-ORG:6974)
+// https://modis-land.gsfc.nasa.gov/GCTP.html
+
+/// Radius of the sphere used by the MODIS sinusoidal grid, in metres
+pub const EARTH_RADIUS: f64 = 6_371_007.181;
+
+/// Project WGS84 latitude/longitude (degrees) to sinusoidal x/y (metres)
+pub fn to_sinusoidal(latitude: f64, longitude: f64) -> (f64, f64) {
+    let lat = latitude.to_radians();
+    let lon = longitude.to_radians();
+    (EARTH_RADIUS * lon * lat.cos(), EARTH_RADIUS * lat)
+}
+
+/// Inverse of [`to_sinusoidal`], returns (latitude, longitude) in degrees
+pub fn to_lat_lon(x: f64, y: f64) -> (f64, f64) {
+    let lat = y / EARTH_RADIUS;
+    let cos_lat = lat.cos();
+    let lon = if cos_lat.abs() < f64::EPSILON {
+        0.0
+    } else {
+        x / (EARTH_RADIUS * cos_lat)
+    };
+    (lat.to_degrees(), lon.to_degrees())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let (x, y) = to_sinusoidal(-45.8667, 170.6667);
+        let (lat, lon) = to_lat_lon(x, y);
+        assert!((lat + 45.8667).abs() < 1e-9);
+        assert!((lon - 170.6667).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_known_corner() {
+        // Lower left corner of the Otago example in examples/lai.rs
+        let (lat, lon) = to_lat_lon(13213215.26, -5101536.36);
+        assert!((lat + 45.8792).abs() < 1e-3);
+        assert!((lon - 170.6889).abs() < 1e-3);
+    }
+}
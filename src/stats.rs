@@ -0,0 +1,252 @@
+// Summary statistics helpers and per-date spatial window statistics
+
+use serde::Serialize;
+
+use crate::dates::ModisDate;
+use crate::mask::{Mask, QcFilter};
+use crate::structs::ModisData;
+
+/// Percentile (0-100) of sorted values with linear interpolation, NaN if empty
+pub fn percentile(sorted: &[f64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return f64::NAN;
+    }
+    let rank = (p.clamp(0.0, 100.0) / 100.0) * (sorted.len() - 1) as f64;
+    let lo = rank.floor() as usize;
+    let hi = rank.ceil() as usize;
+    sorted[lo] + (sorted[hi] - sorted[lo]) * (rank - lo as f64)
+}
+
+/// Solve `a x = b` by Gaussian elimination with partial pivoting, None if singular
+pub fn solve_linear(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+    let n = b.len();
+    for col in 0..n {
+        let pivot =
+            (col..n).max_by(|i, j| a[*i][col].abs().partial_cmp(&a[*j][col].abs()).unwrap())?;
+        if a[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        for row in col + 1..n {
+            let f = a[row][col] / a[col][col];
+            let (top, bottom) = a.split_at_mut(row);
+            for (x, p) in bottom[0][col..].iter_mut().zip(&top[col][col..]) {
+                *x -= f * p;
+            }
+            b[row] -= f * b[col];
+        }
+    }
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let s: f64 = (row + 1..n).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - s) / a[row][row];
+    }
+    Some(x)
+}
+
+/// Weighted least squares fit of `y ~ basis(x)`, returns the coefficients
+pub fn weighted_least_squares(rows: &[Vec<f64>], y: &[f64], w: &[f64]) -> Option<Vec<f64>> {
+    let k = rows.first()?.len();
+    let mut ata = vec![vec![0.0; k]; k];
+    let mut atb = vec![0.0; k];
+    for ((row, y), w) in rows.iter().zip(y).zip(w) {
+        for i in 0..k {
+            atb[i] += w * row[i] * y;
+            for j in 0..k {
+                ata[i][j] += w * row[i] * row[j];
+            }
+        }
+    }
+    solve_linear(ata, atb)
+}
+
+/// Summary of a set of values, statistics are None when there are no values
+/// (and `std` when there is only one, it is the sample standard deviation)
+#[derive(Debug, Clone, PartialEq, Default, Serialize)]
+pub struct Summary {
+    pub count: usize,
+    pub mean: Option<f64>,
+    pub median: Option<f64>,
+    pub std: Option<f64>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    /// (percentile, value)
+    pub percentiles: Vec<(f64, f64)>,
+}
+
+impl Summary {
+    pub fn from_values(values: &[f64], percentiles: &[f64]) -> Summary {
+        let mut sorted: Vec<f64> = values.iter().copied().filter(|v| v.is_finite()).collect();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let n = sorted.len();
+        if n == 0 {
+            return Summary::default();
+        }
+
+        let mean = sorted.iter().sum::<f64>() / n as f64;
+        let std = if n > 1 {
+            Some((sorted.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1) as f64).sqrt())
+        } else {
+            None
+        };
+
+        Summary {
+            count: n,
+            mean: Some(mean),
+            median: Some(percentile(&sorted, 50.0)),
+            std,
+            min: sorted.first().copied(),
+            max: sorted.last().copied(),
+            percentiles: percentiles
+                .iter()
+                .map(|p| (*p, percentile(&sorted, *p)))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Window {
+    /// Every pixel in the subset
+    All,
+    /// Only the pixel containing the requested latitude/longitude
+    Center,
+    /// Pixels within this many rows/cols of the centre pixel, 1 is 3x3, 2 is 5x5
+    Radius(usize),
+}
+
+pub struct WindowOptions<'a> {
+    pub window: Window,
+    /// None uses the built in mask for the band
+    pub mask: Option<Mask>,
+    pub qc: Option<QcFilter<'a>>,
+    pub percentiles: Vec<f64>,
+}
+
+impl Default for WindowOptions<'_> {
+    fn default() -> Self {
+        Self {
+            window: Window::All,
+            mask: None,
+            qc: None,
+            percentiles: vec![10.0, 25.0, 75.0, 90.0],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WindowStats {
+    pub date: ModisDate,
+    /// Pixels in the window, valid or not
+    pub pixels: usize,
+    /// Statistics of the valid (unmasked, QC passing) scaled values, `count` is the valid count
+    pub summary: Summary,
+}
+
+impl ModisData {
+    /// (row, col) of the window centre, the pixel with the requested point or the middle of the grid
+    pub fn center_pixel(&self) -> (usize, usize) {
+        self.pixel_at(self.latitude, self.longitude).unwrap_or((
+            self.nrows.max(0) as usize / 2,
+            self.ncols.max(0) as usize / 2,
+        ))
+    }
+
+    /// Row-major indices of the pixels in `window`
+    pub fn window_indices(&self, window: Window) -> Vec<usize> {
+        let nrows = self.nrows.max(0) as usize;
+        let ncols = self.ncols.max(0) as usize;
+        let (crow, ccol) = self.center_pixel();
+        let radius = match window {
+            Window::All => return (0..nrows * ncols).collect(),
+            Window::Center => 0,
+            Window::Radius(r) => r,
+        };
+        let mut indices = Vec::new();
+        for row in crow.saturating_sub(radius)..(crow + radius + 1).min(nrows) {
+            for col in ccol.saturating_sub(radius)..(ccol + radius + 1).min(ncols) {
+                indices.push(row * ncols + col);
+            }
+        }
+        indices
+    }
+
+    /// Statistics of the window for each date, in date order
+    pub fn window_stats(&self, options: &WindowOptions<'_>) -> Vec<WindowStats> {
+        let mask = options.mask.clone().unwrap_or_else(|| self.mask());
+        let indices = self.window_indices(options.window);
+
+        let mut stats: Vec<WindowStats> = self
+            .subset
+            .iter()
+            .filter_map(|subset| {
+                let date = subset.date()?;
+                let values: Vec<f64> = indices
+                    .iter()
+                    .filter(|i| {
+                        options
+                            .qc
+                            .as_ref()
+                            .map(|qc| qc.passes(&subset.modis_date, **i))
+                            .unwrap_or(true)
+                    })
+                    .filter_map(|i| subset.data.get(*i))
+                    .filter_map(|v| self.masked_value(*v, &mask))
+                    .collect();
+                Some(WindowStats {
+                    date,
+                    pixels: indices.len(),
+                    summary: Summary::from_values(&values, &options.percentiles),
+                })
+            })
+            .collect();
+        stats.sort_by_key(|s| s.date);
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::otago;
+
+    #[test]
+    fn test_summary() {
+        let summary = Summary::from_values(&[1.0, 2.0, 3.0, 4.0], &[25.0]);
+        assert_eq!(summary.mean, Some(2.5));
+        assert_eq!(summary.median, Some(2.5));
+        assert_eq!(summary.percentiles, vec![(25.0, 1.75)]);
+        assert!((summary.std.unwrap() - 1.2909944).abs() < 1e-6);
+        assert_eq!(Summary::from_values(&[], &[]).mean, None);
+    }
+
+    #[test]
+    fn test_window_stats() {
+        let data = otago();
+
+        let all = data.window_stats(&WindowOptions::default());
+        assert_eq!(all[0].pixels, 25);
+        assert_eq!(all[0].summary.count, 20);
+
+        // Centre pixel is 254 (fill)
+        let center = data.window_stats(&WindowOptions {
+            window: Window::Center,
+            ..Default::default()
+        });
+        assert_eq!(center[0].pixels, 1);
+        assert_eq!(center[0].summary.count, 0);
+
+        let mut qc = otago();
+        qc.subset[0].data = (0..25).collect();
+        let three = data.window_stats(&WindowOptions {
+            window: Window::Radius(1),
+            qc: Some(QcFilter::new(&qc, |v| v != 6)),
+            ..Default::default()
+        });
+        // 9, 4, 254, 1, 254, 3, 5, 5, 9 with index 6 (value 9) failing QC
+        assert_eq!(three[0].pixels, 9);
+        assert_eq!(three[0].summary.count, 6);
+        assert_eq!(three[0].summary.max, Some(0.9));
+    }
+}
@@ -0,0 +1,593 @@
+// Local SQLite archive of subsets (feature = "sqlite")
+//
+// A series is one band of one product at one site, its grid and request metadata are kept
+// once and each composite is a row holding the raw values as little-endian i32s. MODIS
+// dates ("AYYYYDDD") sort as text, so date ranges are plain string comparisons.
+
+use std::path::Path;
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+
+use crate::dates::ModisDate;
+use crate::grid::stack;
+use crate::structs::{ModisData, Subset};
+use crate::sync::{new_dates, CursorStore, MAX_DATES_PER_REQUEST};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS series (
+    id INTEGER PRIMARY KEY,
+    site TEXT NOT NULL,
+    product TEXT NOT NULL,
+    band TEXT NOT NULL,
+    latitude REAL NOT NULL,
+    longitude REAL NOT NULL,
+    xllcorner TEXT NOT NULL,
+    yllcorner TEXT NOT NULL,
+    cellsize REAL NOT NULL,
+    nrows INTEGER NOT NULL,
+    ncols INTEGER NOT NULL,
+    units TEXT NOT NULL,
+    scale TEXT NOT NULL,
+    header TEXT NOT NULL,
+    UNIQUE (site, product, band)
+);
+CREATE TABLE IF NOT EXISTS composites (
+    series_id INTEGER NOT NULL REFERENCES series (id),
+    modis_date TEXT NOT NULL,
+    calendar_date TEXT NOT NULL,
+    tile TEXT NOT NULL,
+    proc_date TEXT NOT NULL,
+    data BLOB NOT NULL,
+    PRIMARY KEY (series_id, modis_date)
+);
+CREATE TABLE IF NOT EXISTS reprocessed (
+    series_id INTEGER NOT NULL REFERENCES series (id),
+    modis_date TEXT NOT NULL,
+    old_proc_date TEXT NOT NULL,
+    new_proc_date TEXT NOT NULL,
+    detected_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+CREATE TABLE IF NOT EXISTS cursors (
+    key TEXT PRIMARY KEY,
+    modis_date TEXT NOT NULL
+);
+";
+
+/// A stored series
+///
+/// Location and window are not part of the key, `site` names one location and window per
+/// product and band. [`Store::put`] rejects data for a different location or on a different
+/// grid (window) than the stored series.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+pub struct SeriesKey {
+    pub site: String,
+    pub product: String,
+    pub band: String,
+}
+
+impl SeriesKey {
+    pub fn new(site: &str, product: &str, band: &str) -> SeriesKey {
+        SeriesKey {
+            site: site.to_string(),
+            product: product.to_string(),
+            band: band.to_string(),
+        }
+    }
+}
+
+/// A composite whose `proc_date` differs from the stored one
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Reprocessed {
+    pub modis_date: String,
+    pub old_proc_date: String,
+    pub new_proc_date: String,
+}
+
+/// What [`Store::put`] did with each composite
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct PutReport {
+    /// MODIS dates not stored before
+    pub inserted: Vec<String>,
+    /// Stored composites replaced because they were reprocessed
+    pub reprocessed: Vec<Reprocessed>,
+    /// Composites already stored with the same `proc_date`
+    pub unchanged: usize,
+}
+
+// kmAboveBelow and kmLeftRight of a subset request URL
+fn request_window(header: &str) -> Option<(u8, u8)> {
+    let query = header.split_once('?')?.1;
+    let param = |name: &str| {
+        query
+            .split('&')
+            .find_map(|p| p.strip_prefix(name)?.strip_prefix('='))?
+            .parse()
+            .ok()
+    };
+    Some((param("kmAboveBelow")?, param("kmLeftRight")?))
+}
+
+fn encode(data: &[i32]) -> Vec<u8> {
+    data.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+fn decode(bytes: &[u8]) -> Vec<i32> {
+    bytes
+        .chunks_exact(4)
+        .map(|b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect()
+}
+
+pub struct Store {
+    conn: Connection,
+}
+
+impl Store {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Store, Box<dyn std::error::Error>> {
+        Store::init(Connection::open(path)?)
+    }
+
+    pub fn in_memory() -> Result<Store, Box<dyn std::error::Error>> {
+        Store::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<Store, Box<dyn std::error::Error>> {
+        conn.execute_batch(SCHEMA)?;
+        Ok(Store { conn })
+    }
+
+    fn series_id(&self, key: &SeriesKey) -> Result<Option<i64>, Box<dyn std::error::Error>> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT id FROM series WHERE site = ?1 AND product = ?2 AND band = ?3",
+                params![key.site, key.product, key.band],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    /// Store every composite of `data` under `site`, the product is taken from `header`
+    ///
+    /// New dates are inserted and stored dates with a different `proc_date` are replaced and
+    /// logged as reprocessed. The location and grid must match what is already stored for
+    /// the series.
+    pub fn put(
+        &mut self,
+        site: &str,
+        data: &ModisData,
+    ) -> Result<PutReport, Box<dyn std::error::Error>> {
+        let product = data.product().ok_or("no product in the subset header")?;
+        let key = SeriesKey::new(site, product, &data.band);
+        let tx = self.conn.transaction()?;
+
+        let stored = tx
+            .query_row(
+                "SELECT id, latitude, longitude, xllcorner, yllcorner, cellsize, nrows, ncols
+                 FROM series WHERE site = ?1 AND product = ?2 AND band = ?3",
+                params![key.site, key.product, key.band],
+                |r| {
+                    Ok((
+                        r.get::<_, i64>(0)?,
+                        ModisData {
+                            latitude: r.get(1)?,
+                            longitude: r.get(2)?,
+                            xllcorner: r.get(3)?,
+                            yllcorner: r.get(4)?,
+                            cellsize: r.get(5)?,
+                            nrows: r.get(6)?,
+                            ncols: r.get(7)?,
+                            subset: Vec::new(),
+                            ..data.clone()
+                        },
+                    ))
+                },
+            )
+            .optional()?;
+        let series_id = match stored {
+            Some((id, series)) => {
+                if (series.latitude - data.latitude).abs() > 1e-6
+                    || (series.longitude - data.longitude).abs() > 1e-6
+                {
+                    return Err(format!(
+                        "{:?} is stored for {}, {}, not {}, {}",
+                        key, series.latitude, series.longitude, data.latitude, data.longitude
+                    )
+                    .into());
+                }
+                if !series.same_grid(data) {
+                    return Err(format!("{:?} is stored on a different grid", key).into());
+                }
+                id
+            }
+            None => {
+                tx.execute(
+                    "INSERT INTO series (site, product, band, latitude, longitude, xllcorner,
+                     yllcorner, cellsize, nrows, ncols, units, scale, header)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+                    params![
+                        key.site,
+                        key.product,
+                        key.band,
+                        data.latitude,
+                        data.longitude,
+                        data.xllcorner,
+                        data.yllcorner,
+                        data.cellsize,
+                        data.nrows,
+                        data.ncols,
+                        data.units,
+                        data.scale,
+                        data.header
+                    ],
+                )?;
+                tx.last_insert_rowid()
+            }
+        };
+
+        let mut report = PutReport::default();
+        for subset in &data.subset {
+            let old: Option<String> = tx
+                .query_row(
+                    "SELECT proc_date FROM composites WHERE series_id = ?1 AND modis_date = ?2",
+                    params![series_id, subset.modis_date],
+                    |r| r.get(0),
+                )
+                .optional()?;
+            match old {
+                Some(old) if old == subset.proc_date => {
+                    report.unchanged += 1;
+                    continue;
+                }
+                Some(old) => {
+                    tx.execute(
+                        "INSERT INTO reprocessed (series_id, modis_date, old_proc_date,
+                         new_proc_date) VALUES (?1, ?2, ?3, ?4)",
+                        params![series_id, subset.modis_date, old, subset.proc_date],
+                    )?;
+                    report.reprocessed.push(Reprocessed {
+                        modis_date: subset.modis_date.clone(),
+                        old_proc_date: old,
+                        new_proc_date: subset.proc_date.clone(),
+                    });
+                }
+                None => report.inserted.push(subset.modis_date.clone()),
+            }
+            tx.execute(
+                "INSERT OR REPLACE INTO composites (series_id, modis_date, calendar_date, tile,
+                 proc_date, data) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    series_id,
+                    subset.modis_date,
+                    subset.calendar_date,
+                    subset.tile,
+                    subset.proc_date,
+                    encode(&subset.data)
+                ],
+            )?;
+        }
+        tx.commit()?;
+        Ok(report)
+    }
+
+    /// Every stored series
+    pub fn keys(&self) -> Result<Vec<SeriesKey>, Box<dyn std::error::Error>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT site, product, band FROM series ORDER BY site, product, band")?;
+        let keys = stmt
+            .query_map([], |r| {
+                Ok(SeriesKey {
+                    site: r.get(0)?,
+                    product: r.get(1)?,
+                    band: r.get(2)?,
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+        Ok(keys)
+    }
+
+    /// Latest stored composite date of a series
+    pub fn latest_date(
+        &self,
+        key: &SeriesKey,
+    ) -> Result<Option<ModisDate>, Box<dyn std::error::Error>> {
+        let Some(id) = self.series_id(key)? else {
+            return Ok(None);
+        };
+        let latest: Option<String> = self.conn.query_row(
+            "SELECT MAX(modis_date) FROM composites WHERE series_id = ?1",
+            params![id],
+            |r| r.get(0),
+        )?;
+        Ok(latest.as_deref().and_then(ModisDate::parse))
+    }
+
+    /// Stored composites of a series between `start` and `end` (inclusive, either open),
+    /// None if the series is not stored
+    pub fn get(
+        &self,
+        key: &SeriesKey,
+        start: Option<ModisDate>,
+        end: Option<ModisDate>,
+    ) -> Result<Option<ModisData>, Box<dyn std::error::Error>> {
+        let series = self
+            .conn
+            .query_row(
+                "SELECT id, latitude, longitude, xllcorner, yllcorner, cellsize, nrows, ncols,
+                 units, scale, header FROM series
+                 WHERE site = ?1 AND product = ?2 AND band = ?3",
+                params![key.site, key.product, key.band],
+                |r| {
+                    Ok((
+                        r.get::<_, i64>(0)?,
+                        ModisData {
+                            latitude: r.get(1)?,
+                            longitude: r.get(2)?,
+                            xllcorner: r.get(3)?,
+                            yllcorner: r.get(4)?,
+                            cellsize: r.get(5)?,
+                            nrows: r.get(6)?,
+                            ncols: r.get(7)?,
+                            band: key.band.clone(),
+                            units: r.get(8)?,
+                            scale: r.get(9)?,
+                            header: r.get(10)?,
+                            subset: Vec::new(),
+                        },
+                    ))
+                },
+            )
+            .optional()?;
+        let Some((id, mut data)) = series else {
+            return Ok(None);
+        };
+
+        let start = start.map(|d| d.to_string()).unwrap_or_default();
+        let end = end
+            .map(|d| d.to_string())
+            .unwrap_or_else(|| "B".to_string());
+        let mut stmt = self.conn.prepare(
+            "SELECT modis_date, calendar_date, tile, proc_date, data FROM composites
+             WHERE series_id = ?1 AND modis_date >= ?2 AND modis_date <= ?3
+             ORDER BY modis_date",
+        )?;
+        data.subset = stmt
+            .query_map(params![id, start, end], |r| {
+                Ok(Subset {
+                    modis_date: r.get(0)?,
+                    calendar_date: r.get(1)?,
+                    band: key.band.clone(),
+                    tile: r.get(2)?,
+                    proc_date: r.get(3)?,
+                    data: decode(&r.get::<_, Vec<u8>>(4)?),
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+        Ok(Some(data))
+    }
+
+    /// Every reprocessing logged for a series, oldest first
+    pub fn reprocessed(
+        &self,
+        key: &SeriesKey,
+    ) -> Result<Vec<Reprocessed>, Box<dyn std::error::Error>> {
+        let Some(id) = self.series_id(key)? else {
+            return Ok(Vec::new());
+        };
+        let mut stmt = self.conn.prepare(
+            "SELECT modis_date, old_proc_date, new_proc_date FROM reprocessed
+             WHERE series_id = ?1 ORDER BY rowid",
+        )?;
+        let rows = stmt
+            .query_map(params![id], |r| {
+                Ok(Reprocessed {
+                    modis_date: r.get(0)?,
+                    old_proc_date: r.get(1)?,
+                    new_proc_date: r.get(2)?,
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+        Ok(rows)
+    }
+
+    /// Fetch and store the available composites after the latest stored one, starting at
+    /// `start_date` when the series is empty, in requests of at most
+    /// [`MAX_DATES_PER_REQUEST`] dates
+    ///
+    /// Stored composites are not fetched again, use [`Store::refresh`] to pick up
+    /// reprocessed ones.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn update(
+        &mut self,
+        site: &str,
+        product: &str,
+        latitude: f64,
+        longitude: f64,
+        band: &str,
+        start_date: &str,
+        end_date: &str,
+        km_above_below: u8,
+        km_left_right: u8,
+    ) -> Result<PutReport, Box<dyn std::error::Error>> {
+        let key = SeriesKey::new(site, product, band);
+        let start = match self.latest_date(&key)? {
+            Some(latest) => ModisDate(latest.date().succ_opt().ok_or("date out of range")?),
+            None => start_date.parse()?,
+        };
+        let end: ModisDate = end_date.parse()?;
+        let available = crate::dates(product, latitude, longitude).await?;
+        let dates: Vec<ModisDate> = new_dates(&available.dates, None)
+            .into_iter()
+            .filter(|d| *d >= start && *d <= end)
+            .collect();
+        match fetch(
+            product,
+            latitude,
+            longitude,
+            band,
+            &dates,
+            km_above_below,
+            km_left_right,
+        )
+        .await?
+        {
+            Some(data) => self.put(site, &data),
+            None => Ok(PutReport::default()),
+        }
+    }
+
+    /// Fetch stored composites between `start` and `end` again with the stored location and
+    /// window and [`Store::put`] them, logging any that were reprocessed
+    pub async fn refresh(
+        &mut self,
+        key: &SeriesKey,
+        start: ModisDate,
+        end: ModisDate,
+    ) -> Result<PutReport, Box<dyn std::error::Error>> {
+        let stored = self
+            .get(key, Some(start), Some(end))?
+            .ok_or_else(|| format!("{:?} is not stored", key))?;
+        let (km_above_below, km_left_right) = request_window(&stored.header)
+            .ok_or("stored header has no kmAboveBelow/kmLeftRight")?;
+        let dates: Vec<ModisDate> = stored.subset.iter().filter_map(|s| s.date()).collect();
+        match fetch(
+            &key.product,
+            stored.latitude,
+            stored.longitude,
+            &key.band,
+            &dates,
+            km_above_below,
+            km_left_right,
+        )
+        .await?
+        {
+            Some(data) => self.put(&key.site, &data),
+            None => Ok(PutReport::default()),
+        }
+    }
+}
+
+// Subsets of `dates` (sorted) in requests of at most MAX_DATES_PER_REQUEST dates, stacked,
+// None when there are no dates
+async fn fetch(
+    product: &str,
+    latitude: f64,
+    longitude: f64,
+    band: &str,
+    dates: &[ModisDate],
+    km_above_below: u8,
+    km_left_right: u8,
+) -> Result<Option<ModisData>, Box<dyn std::error::Error>> {
+    let mut parts = Vec::new();
+    for chunk in dates.chunks(MAX_DATES_PER_REQUEST) {
+        parts.push(
+            crate::subset(
+                product,
+                latitude,
+                longitude,
+                band,
+                &chunk[0].to_string(),
+                &chunk[chunk.len() - 1].to_string(),
+                km_above_below,
+                km_left_right,
+            )
+            .await?,
+        );
+    }
+    if parts.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(stack(&parts)?))
+}
+
+/// Sync cursors in the same database as the archive
+impl CursorStore for Store {
+    fn load(&self, key: &str) -> Result<Option<ModisDate>, Box<dyn std::error::Error>> {
+        let date: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT modis_date FROM cursors WHERE key = ?1",
+                params![key],
+                |r| r.get(0),
+            )
+            .optional()?;
+        Ok(date.as_deref().and_then(ModisDate::parse))
+    }
+
+    fn save(&mut self, key: &str, cursor: ModisDate) -> Result<(), Box<dyn std::error::Error>> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO cursors (key, modis_date) VALUES (?1, ?2)",
+            params![key, cursor.to_string()],
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::otago;
+
+    #[test]
+    fn test_store() {
+        let mut store = Store::in_memory().unwrap();
+        let mut data = otago();
+        data.header =
+            "https://modis.ornl.gov/rst/api/v1/MCD15A2H/subset?latitude=-45.8667".to_string();
+        let mut later = data.subset[0].clone();
+        later.modis_date = "A2024225".to_string();
+        later.calendar_date = "2024-08-12".to_string();
+        later.data = vec![5; 25];
+        data.subset.push(later);
+
+        let report = store.put("otago", &data).unwrap();
+        assert_eq!(report.inserted, vec!["A2024217", "A2024225"]);
+        let key = SeriesKey::new("otago", "MCD15A2H", "Lai_500m");
+        assert_eq!(store.keys().unwrap(), vec![key.clone()]);
+        assert_eq!(
+            store.latest_date(&key).unwrap(),
+            ModisDate::parse("A2024225")
+        );
+
+        // Same data again, then one composite reprocessed
+        assert_eq!(store.put("otago", &data).unwrap().unchanged, 2);
+        data.subset[1].proc_date = "2024-09-01".to_string();
+        data.subset[1].data[0] = 6;
+        let report = store.put("otago", &data).unwrap();
+        assert_eq!(report.reprocessed.len(), 1);
+        assert_eq!(report.reprocessed[0].modis_date, "A2024225");
+        assert_eq!(store.reprocessed(&key).unwrap(), report.reprocessed);
+
+        let all = store.get(&key, None, None).unwrap().unwrap();
+        assert!(all.same_grid(&data));
+        assert_eq!(all.subset.len(), 2);
+        assert_eq!(all.subset[0].data, data.subset[0].data);
+        assert_eq!(all.subset[1].data[0], 6);
+        let late = store
+            .get(&key, ModisDate::parse("2024-08-10"), None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(late.subset.len(), 1);
+        assert!(store
+            .get(&SeriesKey::new("otago", "MOD13Q1", "x"), None, None)
+            .unwrap()
+            .is_none());
+
+        let date = ModisDate::parse("A2024225").unwrap();
+        store.save("otago/MCD15A2H/Lai_500m", date).unwrap();
+        assert_eq!(store.load("otago/MCD15A2H/Lai_500m").unwrap(), Some(date));
+
+        // Refreshes reuse the request window of the stored header
+        assert_eq!(request_window(&otago().header), Some((1, 1)));
+        assert_eq!(request_window(&all.header), None);
+
+        let mut moved = data.clone();
+        moved.xllcorner = "0".to_string();
+        assert!(store.put("otago", &moved).is_err());
+        let mut elsewhere = data.clone();
+        elsewhere.latitude += 0.001;
+        assert!(store.put("otago", &elsewhere).is_err());
+    }
+}
@@ -1,6 +1,7 @@
 use serde::de::{self, Deserializer};
 use serde::ser::Serializer;
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
 
 #[derive(Deserialize, Serialize, Debug, PartialEq, Eq, Clone)]
 pub struct DateInfo {
@@ -26,7 +27,8 @@ pub struct ProductsData {
     pub products: Vec<Product>,
 }
 
-#[derive(Debug)]
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ProductType {
     Daymet,
     ECO4ESIPTJPL,
@@ -191,9 +193,56 @@ impl<'de> Deserialize<'de> for ProductType {
     }
 }
 
-impl Into<&str> for ProductType {
-    fn into(self) -> &'static str {
+impl FromStr for ProductType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let deserializer = de::value::BorrowedStrDeserializer::<de::value::Error>::new(s);
+        ProductType::deserialize(deserializer).map_err(|_| format!("unknown product: {}", s))
+    }
+}
+
+impl ProductType {
+    /// Days per composite, None for yearly, monthly and irregular products
+    pub fn composite_days(&self) -> Option<u32> {
         match self {
+            ProductType::Daymet
+            | ProductType::MCD43A
+            | ProductType::MCD43A1
+            | ProductType::MCD43A4 => Some(1),
+            ProductType::MCD15A3H => Some(4),
+            ProductType::MCD15A2H
+            | ProductType::MOD09A1
+            | ProductType::MOD11A2
+            | ProductType::MOD14A2
+            | ProductType::MOD15A2H
+            | ProductType::MOD16A2
+            | ProductType::MOD16A2GF
+            | ProductType::MOD17A2H
+            | ProductType::MOD17A2HGF
+            | ProductType::MOD21A2
+            | ProductType::MYD09A1
+            | ProductType::MYD11A2
+            | ProductType::MYD14A2
+            | ProductType::MYD15A2H
+            | ProductType::MYD16A2
+            | ProductType::MYD16A2GF
+            | ProductType::MYD17A2H
+            | ProductType::MYD17A2HGF
+            | ProductType::MYD21A2
+            | ProductType::VNP09A1
+            | ProductType::VNP09H1
+            | ProductType::VNP15A2H
+            | ProductType::VNP21A2 => Some(8),
+            ProductType::MOD13Q1 | ProductType::MYD13Q1 | ProductType::VNP13A1 => Some(16),
+            _ => None,
+        }
+    }
+}
+
+impl From<ProductType> for &'static str {
+    fn from(product: ProductType) -> &'static str {
+        match product {
             ProductType::Daymet => "Daymet",
             ProductType::ECO4ESIPTJPL => "ECO4ESIPTJPL",
             ProductType::ECO4WUE => "ECO4WUE",
@@ -244,7 +293,41 @@ impl Into<&str> for ProductType {
     }
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Band {
+    pub band: String,
+    pub description: String,
+    pub units: String,
+    pub scale_factor: Option<String>,
+    pub fill_value: Option<String>,
+    pub valid_range: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct BandsData {
+    pub bands: Vec<Band>,
+}
+
+impl Band {
+    pub fn scale_factor(&self) -> Option<f64> {
+        match self.scale_factor.as_deref()?.trim().parse::<f64>() {
+            Ok(scale) if scale != 0.0 && scale.is_finite() => Some(scale),
+            _ => None,
+        }
+    }
+
+    pub fn fill_value(&self) -> Option<i32> {
+        self.fill_value.as_deref()?.trim().parse().ok()
+    }
+
+    /// Valid range, the API reports it as e.g. "-2000 to 10000"
+    pub fn valid_range(&self) -> Option<(i32, i32)> {
+        let (min, max) = self.valid_range.as_deref()?.split_once("to")?;
+        Some((min.trim().parse().ok()?, max.trim().parse().ok()?))
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Site {
     pub siteid: String,
     pub sitename: String,
@@ -255,12 +338,12 @@ pub struct Site {
     pub country: String,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Sites {
     pub sites: Vec<Site>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Subset {
     pub modis_date: String,
     pub calendar_date: String,
@@ -270,7 +353,7 @@ pub struct Subset {
     pub data: Vec<i32>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct ModisData {
     pub xllcorner: String,
     pub yllcorner: String,
@@ -0,0 +1,214 @@
+// Incremental sync of a site, fetching only composites newer than a saved cursor
+//
+// The cursor is the latest composite date fetched so far. It lives behind CursorStore so
+// a nightly job can keep it in memory, a JSON file or the SQLite store.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::dates::ModisDate;
+use crate::grid::stack;
+use crate::structs::{DateInfo, ModisData, Subset};
+
+/// Most dates the subset endpoint returns per request
+pub const MAX_DATES_PER_REQUEST: usize = 10;
+
+/// Persisted sync cursors, keyed by [`SyncTarget::key`]
+pub trait CursorStore {
+    fn load(&self, key: &str) -> Result<Option<ModisDate>, Box<dyn std::error::Error>>;
+    fn save(&mut self, key: &str, cursor: ModisDate) -> Result<(), Box<dyn std::error::Error>>;
+}
+
+/// Cursors kept for the life of the process
+#[derive(Debug, Clone, Default)]
+pub struct MemoryCursors {
+    pub cursors: BTreeMap<String, ModisDate>,
+}
+
+impl CursorStore for MemoryCursors {
+    fn load(&self, key: &str) -> Result<Option<ModisDate>, Box<dyn std::error::Error>> {
+        Ok(self.cursors.get(key).copied())
+    }
+
+    fn save(&mut self, key: &str, cursor: ModisDate) -> Result<(), Box<dyn std::error::Error>> {
+        self.cursors.insert(key.to_string(), cursor);
+        Ok(())
+    }
+}
+
+/// Cursors in a JSON object of key to MODIS date, the file is created on first save
+#[derive(Debug, Clone)]
+pub struct FileCursors {
+    pub path: PathBuf,
+}
+
+impl FileCursors {
+    pub fn new<P: Into<PathBuf>>(path: P) -> FileCursors {
+        FileCursors { path: path.into() }
+    }
+
+    fn read(&self) -> Result<BTreeMap<String, String>, Box<dyn std::error::Error>> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(text) => Ok(serde_json::from_str(&text)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl CursorStore for FileCursors {
+    fn load(&self, key: &str) -> Result<Option<ModisDate>, Box<dyn std::error::Error>> {
+        match self.read()?.get(key) {
+            Some(date) => Ok(Some(date.parse()?)),
+            None => Ok(None),
+        }
+    }
+
+    fn save(&mut self, key: &str, cursor: ModisDate) -> Result<(), Box<dyn std::error::Error>> {
+        let mut cursors = self.read()?;
+        cursors.insert(key.to_string(), cursor.to_string());
+        // Write then rename so a crash never leaves half a file
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(&cursors)?)?;
+        std::fs::rename(tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+/// What to keep in sync
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyncTarget {
+    pub site: String,
+    pub product: String,
+    pub band: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub km_above_below: u8,
+    pub km_left_right: u8,
+}
+
+impl SyncTarget {
+    /// "site/product/band"
+    pub fn key(&self) -> String {
+        format!("{}/{}/{}", self.site, self.product, self.band)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SyncResult {
+    /// New composites on the site grid, None when there was nothing new
+    pub data: Option<ModisData>,
+    /// Latest composite fetched so far, saved to the cursor store
+    pub cursor: Option<ModisDate>,
+}
+
+impl SyncResult {
+    pub fn subsets(&self) -> &[Subset] {
+        self.data.as_ref().map(|d| &d.subset[..]).unwrap_or(&[])
+    }
+}
+
+/// Available dates after `cursor`, sorted, all of them when there is no cursor
+pub fn new_dates(available: &[DateInfo], cursor: Option<ModisDate>) -> Vec<ModisDate> {
+    let mut dates: Vec<ModisDate> = available
+        .iter()
+        .filter_map(|d| ModisDate::parse(&d.modis_date))
+        .filter(|d| cursor.map(|c| *d > c).unwrap_or(true))
+        .collect();
+    dates.sort();
+    dates.dedup();
+    dates
+}
+
+/// Fetch the composites of `target` published after its saved cursor and advance it
+///
+/// Without a saved cursor `initial` is used as the last known date, and when that is None
+/// too only the newest composite is fetched. The cursor is saved only after every request
+/// succeeded, so a failed run is retried in full next time.
+pub async fn sync<C: CursorStore>(
+    target: &SyncTarget,
+    cursors: &mut C,
+    initial: Option<ModisDate>,
+) -> Result<SyncResult, Box<dyn std::error::Error>> {
+    let key = target.key();
+    let cursor = cursors.load(&key)?.or(initial);
+    let available = crate::dates(&target.product, target.latitude, target.longitude).await?;
+    let mut dates = new_dates(&available.dates, cursor);
+    if cursor.is_none() {
+        dates = dates.split_off(dates.len().saturating_sub(1));
+    }
+
+    let mut parts = Vec::new();
+    for chunk in dates.chunks(MAX_DATES_PER_REQUEST) {
+        parts.push(
+            crate::subset(
+                &target.product,
+                target.latitude,
+                target.longitude,
+                &target.band,
+                &chunk[0].to_string(),
+                &chunk[chunk.len() - 1].to_string(),
+                target.km_above_below,
+                target.km_left_right,
+            )
+            .await?,
+        );
+    }
+    if parts.is_empty() {
+        return Ok(SyncResult { data: None, cursor });
+    }
+
+    let data = stack(&parts)?;
+    let latest = data.subset.iter().filter_map(|s| s.date()).max();
+    let cursor = cursor.max(latest);
+    if let Some(c) = cursor {
+        cursors.save(&key, c)?;
+    }
+    Ok(SyncResult {
+        data: Some(data),
+        cursor,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(modis_date: &str) -> DateInfo {
+        DateInfo {
+            modis_date: modis_date.to_string(),
+            calendar_date: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_new_dates() {
+        let available = [info("A2024225"), info("A2024209"), info("A2024217")];
+        let cursor = ModisDate::parse("A2024209");
+        let dates = new_dates(&available, cursor);
+        assert_eq!(dates.len(), 2);
+        assert_eq!(dates[0].to_string(), "A2024217");
+        assert_eq!(new_dates(&available, None).len(), 3);
+        assert!(new_dates(&available, ModisDate::parse("A2024225")).is_empty());
+    }
+
+    #[test]
+    fn test_cursors() {
+        let path = std::env::temp_dir().join(format!("cursors-{}.json", std::process::id()));
+        let mut file = FileCursors::new(&path);
+        assert_eq!(file.load("otago/MCD15A2H/Lai_500m").unwrap(), None);
+        let date = ModisDate::parse("A2024217").unwrap();
+        file.save("otago/MCD15A2H/Lai_500m", date).unwrap();
+        assert_eq!(
+            FileCursors::new(&path)
+                .load("otago/MCD15A2H/Lai_500m")
+                .unwrap(),
+            Some(date)
+        );
+        std::fs::remove_file(path).unwrap();
+
+        let mut memory = MemoryCursors::default();
+        memory.save("a", date).unwrap();
+        assert_eq!(memory.load("a").unwrap(), Some(date));
+    }
+}
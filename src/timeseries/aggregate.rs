@@ -0,0 +1,282 @@
+// Resampling composites to calendar months, seasons and years
+//
+// Each composite covers [date, date + composite_days), cut short at the end of the year as
+// MODIS composites restart on 1 January. A composite that straddles two periods contributes
+// to both, weighted by the days that fall in each.
+
+use std::collections::BTreeMap;
+
+use chrono::{Datelike, Months, NaiveDate};
+use serde::Serialize;
+
+use crate::dates::ModisDate;
+use crate::structs::ModisData;
+use crate::timeseries::TimeSeries;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Period {
+    Monthly,
+    /// Meteorological seasons, DJF, MAM, JJA and SON (December counts towards the next year)
+    Seasonal,
+    Annual,
+    /// Twelve months from `start_month`, e.g. 10 for the USGS water year or 7 for
+    /// southern hemisphere hydrological years
+    HydrologicalYear {
+        start_month: u32,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reducer {
+    /// Mean weighted by the days each composite contributes
+    Mean,
+    /// Maximum value composite, any composite overlapping the period counts
+    Max,
+    /// For period totals such as MOD17A2H GPP or MOD16A2 ET, each composite adds the
+    /// fraction of its total that falls in the period
+    Sum,
+    /// Median weighted by the days each composite contributes
+    Median,
+    /// Number of valid composites overlapping the period
+    Count,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PeriodValue {
+    pub start: NaiveDate,
+    /// Last day of the period
+    pub end: NaiveDate,
+    pub value: Option<f64>,
+    /// Valid composites overlapping the period
+    pub count: usize,
+    /// Fraction (0-1) of the period's days covered by valid composites
+    pub coverage: f64,
+}
+
+/// Per-period grids from [`ModisData::aggregate`], values row-major
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PeriodGrid {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub values: Vec<Option<f64>>,
+    pub coverage: Vec<f64>,
+}
+
+fn first_of_month(year: i32, month: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, 1).unwrap()
+}
+
+impl Period {
+    /// (first day, first day of the next period) of the period containing `date`
+    pub fn bounds(&self, date: NaiveDate) -> (NaiveDate, NaiveDate) {
+        let (year, month) = (date.year(), date.month());
+        let (start, months) = match self {
+            Period::Monthly => (first_of_month(year, month), 1),
+            Period::Seasonal => {
+                let start = match month {
+                    12 => first_of_month(year, 12),
+                    1 | 2 => first_of_month(year - 1, 12),
+                    m => first_of_month(year, m - m % 3),
+                };
+                (start, 3)
+            }
+            Period::Annual => (first_of_month(year, 1), 12),
+            Period::HydrologicalYear { start_month } => {
+                let s = (*start_month).clamp(1, 12);
+                if month >= s {
+                    (first_of_month(year, s), 12)
+                } else {
+                    (first_of_month(year - 1, s), 12)
+                }
+            }
+        };
+        (start, start + Months::new(months))
+    }
+}
+
+/// Days covered by the composite starting at `date`
+pub fn composite_span(date: ModisDate, composite_days: u32) -> (NaiveDate, NaiveDate) {
+    let start = date.date();
+    let next_year = first_of_month(date.year() + 1, 1);
+    let end = (start + chrono::Duration::days(composite_days.max(1) as i64)).min(next_year);
+    (start, end)
+}
+
+// (value, overlap days, composite days)
+type Contribution = (f64, f64, f64);
+
+fn days(a: NaiveDate, b: NaiveDate) -> f64 {
+    (b - a).num_days() as f64
+}
+
+fn weighted_median(mut values: Vec<(f64, f64)>) -> Option<f64> {
+    values.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+    let total: f64 = values.iter().map(|(_, w)| w).sum();
+    let mut cumulative = 0.0;
+    for (value, weight) in &values {
+        cumulative += weight;
+        if cumulative >= total / 2.0 {
+            return Some(*value);
+        }
+    }
+    None
+}
+
+/// Aggregate a series to calendar periods, every period touched by a composite is returned
+/// (with no value and zero coverage when all of its composites are masked)
+pub fn aggregate(
+    series: &TimeSeries,
+    composite_days: u32,
+    period: Period,
+    reducer: Reducer,
+) -> Vec<PeriodValue> {
+    let mut periods: BTreeMap<(NaiveDate, NaiveDate), Vec<Contribution>> = BTreeMap::new();
+    for (date, value) in series.iter() {
+        let (start, end) = composite_span(*date, composite_days);
+        let mut bounds = period.bounds(start);
+        while bounds.0 < end {
+            let overlap = days(start.max(bounds.0), end.min(bounds.1));
+            let contributions = periods.entry(bounds).or_default();
+            if let Some(value) = value {
+                contributions.push((*value, overlap, days(start, end)));
+            }
+            bounds = period.bounds(bounds.1);
+        }
+    }
+
+    periods
+        .into_iter()
+        .map(|((start, end), contributions)| {
+            let covered: f64 = contributions.iter().map(|(_, overlap, _)| overlap).sum();
+            let value = if contributions.is_empty() {
+                None
+            } else {
+                match reducer {
+                    Reducer::Mean => {
+                        Some(contributions.iter().map(|(v, o, _)| v * o).sum::<f64>() / covered)
+                    }
+                    Reducer::Max => contributions.iter().map(|(v, _, _)| *v).reduce(f64::max),
+                    Reducer::Sum => Some(contributions.iter().map(|(v, o, d)| v * o / d).sum()),
+                    Reducer::Median => {
+                        weighted_median(contributions.iter().map(|(v, o, _)| (*v, *o)).collect())
+                    }
+                    Reducer::Count => Some(contributions.len() as f64),
+                }
+            };
+            PeriodValue {
+                start,
+                end: end.pred_opt().unwrap(),
+                value,
+                count: contributions.len(),
+                coverage: (covered / days(start, end)).min(1.0),
+            }
+        })
+        .collect()
+}
+
+impl ModisData {
+    /// Aggregate every pixel to calendar periods, `composite_days` defaults to the product's
+    /// composite period. Use [`crate::grid::stack`] first to combine several requests.
+    pub fn aggregate(
+        &self,
+        composite_days: Option<u32>,
+        period: Period,
+        reducer: Reducer,
+    ) -> Result<Vec<PeriodGrid>, Box<dyn std::error::Error>> {
+        let composite_days = composite_days
+            .or(self.composite_days())
+            .ok_or("unknown composite period, pass composite_days")?;
+        // Every pixel has the same dates, so the same periods in the same order
+        let mut grids: Vec<PeriodGrid> = Vec::new();
+        for (_, series) in self.pixel_series_iter() {
+            let values = aggregate(&series, composite_days, period, reducer);
+            if grids.is_empty() {
+                grids = values
+                    .iter()
+                    .map(|p| PeriodGrid {
+                        start: p.start,
+                        end: p.end,
+                        values: Vec::new(),
+                        coverage: Vec::new(),
+                    })
+                    .collect();
+            }
+            for (grid, p) in grids.iter_mut().zip(values) {
+                grid.values.push(p.value);
+                grid.coverage.push(p.coverage);
+            }
+        }
+        Ok(grids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::otago;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_bounds() {
+        assert_eq!(
+            Period::Seasonal.bounds(date("2024-01-15")),
+            (date("2023-12-01"), date("2024-03-01"))
+        );
+        assert_eq!(
+            Period::HydrologicalYear { start_month: 10 }.bounds(date("2024-03-15")),
+            (date("2023-10-01"), date("2024-10-01"))
+        );
+        // Last 8-day composite of 2023 is only 5 days
+        let last = ModisDate::parse("A2023361").unwrap();
+        assert_eq!(composite_span(last, 8).1, date("2024-01-01"));
+    }
+
+    #[test]
+    fn test_aggregate() {
+        // 8-day composites over January and February 2024, GPP style totals of 8 per composite
+        let series = TimeSeries::new(
+            (0..8)
+                .map(|k| {
+                    let date = ModisDate::from_year_doy(2024, 1 + k * 8).unwrap();
+                    (date, if k == 7 { None } else { Some(8.0) })
+                })
+                .collect(),
+        );
+
+        let sums = aggregate(&series, 8, Period::Monthly, Reducer::Sum);
+        assert_eq!(sums.len(), 3);
+        assert_eq!(sums[0].start, date("2024-01-01"));
+        assert_eq!(sums[0].end, date("2024-01-31"));
+        assert_eq!(sums[0].value, Some(31.0));
+        assert_eq!(sums[0].coverage, 1.0);
+        // The composite starting 25 Jan puts 1 day into February, then three full composites
+        // with the masked one from 26 Feb left out, 1 + 8 + 8 + 8 = 25 of 29 days
+        assert_eq!(sums[1].value, Some(25.0));
+        assert!((sums[1].coverage - 25.0 / 29.0).abs() < 1e-9);
+        assert_eq!(sums[1].count, 4);
+        // Only the masked composite reaches March
+        assert_eq!(sums[2].value, None);
+        assert_eq!(sums[2].coverage, 0.0);
+
+        let means = aggregate(&series, 8, Period::Monthly, Reducer::Mean);
+        assert_eq!(means[0].value, Some(8.0));
+        let counts = aggregate(&series, 8, Period::Annual, Reducer::Count);
+        assert_eq!(counts[0].value, Some(7.0));
+    }
+
+    #[test]
+    fn test_aggregate_grid() {
+        let grids = otago()
+            .aggregate(None, Period::Monthly, Reducer::Max)
+            .unwrap();
+        // 4 Aug composite runs to 11 Aug
+        assert_eq!(grids.len(), 1);
+        assert_eq!(grids[0].values.len(), 25);
+        assert_eq!(grids[0].values[0], Some(1.3));
+        assert_eq!(grids[0].values[8], None);
+        assert!((grids[0].coverage[0] - 8.0 / 31.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,196 @@
+// Long term climatology per composite period and anomalies against it
+//
+// Dates are aligned on the composite slot within the year, (doy - 1) / composite_days, so
+// each 8-day or 16-day composite is compared with the same composite in other years.
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::dates::ModisDate;
+use crate::stats::Summary;
+use crate::structs::ModisData;
+use crate::timeseries::TimeSeries;
+
+/// Composite slot of a date within its year
+pub fn composite_slot(date: ModisDate, composite_days: u32) -> u32 {
+    (date.doy() - 1) / composite_days.max(1)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Climatology {
+    pub composite_days: u32,
+    /// Summary of the values in each composite slot
+    pub slots: BTreeMap<u32, Summary>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Anomaly {
+    pub date: ModisDate,
+    pub value: f64,
+    pub mean: f64,
+    /// value - mean
+    pub absolute: f64,
+    /// value / mean * 100
+    pub percent_of_normal: Option<f64>,
+    /// (value - mean) / std
+    pub z_score: Option<f64>,
+    /// Vegetation Condition Index, (value - min) / (max - min) * 100
+    pub vci: Option<f64>,
+    /// Temperature Condition Index, (max - value) / (max - min) * 100
+    pub tci: Option<f64>,
+}
+
+impl Climatology {
+    /// Climatology of the valid values, optionally only from the baseline years (inclusive)
+    pub fn from_series(
+        series: &TimeSeries,
+        composite_days: u32,
+        percentiles: &[f64],
+        baseline: Option<(i32, i32)>,
+    ) -> Climatology {
+        let mut values: BTreeMap<u32, Vec<f64>> = BTreeMap::new();
+        for (date, value) in series.valid() {
+            if let Some((first, last)) = baseline {
+                if date.year() < first || date.year() > last {
+                    continue;
+                }
+            }
+            values
+                .entry(composite_slot(date, composite_days))
+                .or_default()
+                .push(value);
+        }
+        Climatology {
+            composite_days,
+            slots: values
+                .into_iter()
+                .map(|(slot, v)| (slot, Summary::from_values(&v, percentiles)))
+                .collect(),
+        }
+    }
+
+    /// Normal for the composite containing `date`
+    pub fn normal(&self, date: ModisDate) -> Option<&Summary> {
+        self.slots.get(&composite_slot(date, self.composite_days))
+    }
+
+    pub fn anomaly(&self, date: ModisDate, value: f64) -> Option<Anomaly> {
+        let normal = self.normal(date)?;
+        let mean = normal.mean?;
+        let range = match (normal.min, normal.max) {
+            (Some(min), Some(max)) if max > min => Some((min, max)),
+            _ => None,
+        };
+        Some(Anomaly {
+            date,
+            value,
+            mean,
+            absolute: value - mean,
+            percent_of_normal: if mean != 0.0 {
+                Some(value / mean * 100.0)
+            } else {
+                None
+            },
+            z_score: normal
+                .std
+                .filter(|std| *std > 0.0)
+                .map(|std| (value - mean) / std),
+            vci: range.map(|(min, max)| (value - min) / (max - min) * 100.0),
+            tci: range.map(|(min, max)| (max - value) / (max - min) * 100.0),
+        })
+    }
+
+    /// Anomalies for every valid point with a normal
+    pub fn anomalies(&self, series: &TimeSeries) -> Vec<Anomaly> {
+        series
+            .valid()
+            .filter_map(|(date, value)| self.anomaly(date, value))
+            .collect()
+    }
+}
+
+impl ModisData {
+    /// Days per composite for this product, see [`crate::ProductType::composite_days`]
+    pub fn composite_days(&self) -> Option<u32> {
+        self.product_type()?.composite_days()
+    }
+
+    /// Per pixel climatology (row-major) of a multi-year ModisData, see [`crate::grid::stack`]
+    /// to combine several requests, `composite_days` defaults to the product's composite period
+    pub fn climatology(
+        &self,
+        composite_days: Option<u32>,
+        percentiles: &[f64],
+        baseline: Option<(i32, i32)>,
+    ) -> Result<Vec<Climatology>, Box<dyn std::error::Error>> {
+        let composite_days = composite_days
+            .or(self.composite_days())
+            .ok_or("unknown composite period, pass composite_days")?;
+        Ok(self
+            .pixel_series_iter()
+            .map(|(_, series)| {
+                Climatology::from_series(&series, composite_days, percentiles, baseline)
+            })
+            .collect())
+    }
+
+    /// Per pixel anomalies (row-major) against a climatology from [`ModisData::climatology`]
+    pub fn anomalies(&self, climatology: &[Climatology]) -> Vec<Vec<Anomaly>> {
+        self.pixel_series_iter()
+            .zip(climatology)
+            .map(|((_, series), climatology)| climatology.anomalies(&series))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structs::ProductType;
+    use crate::tests::otago;
+    use chrono::Duration;
+
+    #[test]
+    fn test_climatology() {
+        // Five years of 16-day composites, the last year is 20% down
+        let mut points = Vec::new();
+        for year in 2015..2020 {
+            let start = ModisDate::from_year_doy(year, 1).unwrap();
+            for k in 0..23 {
+                let date = ModisDate(start.date() + Duration::days(k * 16));
+                let value = 0.5 + 0.01 * (year - 2015) as f64 + 0.01 * k as f64;
+                let value = if year == 2019 { value * 0.8 } else { value };
+                points.push((date, Some(value)));
+            }
+        }
+        let series = TimeSeries::new(points);
+
+        let climatology = Climatology::from_series(&series, 16, &[10.0, 90.0], Some((2015, 2018)));
+        assert_eq!(climatology.slots.len(), 23);
+        assert_eq!(climatology.slots[&0].count, 4);
+
+        let date = ModisDate::from_year_doy(2019, 17).unwrap();
+        let value = series.get(date).unwrap();
+        let anomaly = climatology.anomaly(date, value).unwrap();
+        assert!((anomaly.mean - 0.525).abs() < 1e-9);
+        assert!(anomaly.absolute < 0.0);
+        assert!(anomaly.percent_of_normal.unwrap() < 100.0);
+        assert!(anomaly.z_score.unwrap() < -1.0);
+        assert!(anomaly.vci.unwrap() < 0.0);
+        assert!(anomaly.tci.unwrap() > 100.0);
+
+        assert_eq!(climatology.anomalies(&series).len(), 5 * 23);
+    }
+
+    #[test]
+    fn test_composite_days() {
+        let data = otago();
+        assert_eq!("MCD15A2H".parse::<ProductType>(), Ok(ProductType::MCD15A2H));
+        assert_eq!(data.product_type(), Some(ProductType::MCD15A2H));
+        assert_eq!(data.composite_days(), Some(8));
+        let climatology = data.climatology(None, &[], None).unwrap();
+        assert_eq!(climatology.len(), 25);
+        assert_eq!(climatology[0].composite_days, 8);
+    }
+}
@@ -0,0 +1,164 @@
+// Per-pixel time series
+// ModisData is organised by date, these pull one pixel out across all dates
+
+pub mod aggregate;
+pub mod climatology;
+pub mod phenology;
+pub mod smooth;
+
+use serde::Serialize;
+
+use crate::dates::ModisDate;
+use crate::mask::Mask;
+use crate::structs::ModisData;
+
+/// Date ordered series, None where the value is masked (fill or out of the valid range)
+#[derive(Debug, Clone, PartialEq, Default, Serialize)]
+pub struct TimeSeries {
+    pub points: Vec<(ModisDate, Option<f64>)>,
+}
+
+impl TimeSeries {
+    /// Sorted by date
+    pub fn new(mut points: Vec<(ModisDate, Option<f64>)>) -> TimeSeries {
+        points.sort_by_key(|(date, _)| *date);
+        TimeSeries { points }
+    }
+
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &(ModisDate, Option<f64>)> {
+        self.points.iter()
+    }
+
+    pub fn dates(&self) -> impl Iterator<Item = ModisDate> + '_ {
+        self.points.iter().map(|(date, _)| *date)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = Option<f64>> + '_ {
+        self.points.iter().map(|(_, value)| *value)
+    }
+
+    /// Only the unmasked points
+    pub fn valid(&self) -> impl Iterator<Item = (ModisDate, f64)> + '_ {
+        self.points
+            .iter()
+            .filter_map(|(date, value)| value.map(|v| (*date, v)))
+    }
+
+    pub fn valid_count(&self) -> usize {
+        self.valid().count()
+    }
+
+    pub fn get(&self, date: ModisDate) -> Option<f64> {
+        self.points
+            .iter()
+            .find(|(d, _)| *d == date)
+            .and_then(|(_, v)| *v)
+    }
+}
+
+impl IntoIterator for TimeSeries {
+    type Item = (ModisDate, Option<f64>);
+    type IntoIter = std::vec::IntoIter<(ModisDate, Option<f64>)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.points.into_iter()
+    }
+}
+
+impl FromIterator<(ModisDate, Option<f64>)> for TimeSeries {
+    fn from_iter<I: IntoIterator<Item = (ModisDate, Option<f64>)>>(iter: I) -> Self {
+        TimeSeries::new(iter.into_iter().collect())
+    }
+}
+
+impl ModisData {
+    /// Series for the pixel at (row, col) with the built in band mask, None if outside the grid
+    pub fn pixel_series(&self, row: usize, col: usize) -> Option<TimeSeries> {
+        self.pixel_series_with_mask(row, col, &self.mask())
+    }
+
+    pub fn pixel_series_with_mask(
+        &self,
+        row: usize,
+        col: usize,
+        mask: &Mask,
+    ) -> Option<TimeSeries> {
+        if row >= self.nrows.max(0) as usize || col >= self.ncols.max(0) as usize {
+            return None;
+        }
+        let i = row * self.ncols as usize + col;
+        Some(
+            self.subset
+                .iter()
+                .filter_map(|subset| {
+                    let date = subset.date()?;
+                    let value = subset.data.get(i).and_then(|v| self.masked_value(*v, mask));
+                    Some((date, value))
+                })
+                .collect(),
+        )
+    }
+
+    /// Series for the pixel containing the requested `latitude`/`longitude`
+    pub fn center_series(&self) -> Option<TimeSeries> {
+        self.series_at(self.latitude, self.longitude)
+    }
+
+    /// Series for the pixel containing the given point, None if it is outside the grid
+    pub fn series_at(&self, latitude: f64, longitude: f64) -> Option<TimeSeries> {
+        let (row, col) = self.pixel_at(latitude, longitude)?;
+        self.pixel_series(row, col)
+    }
+
+    /// ((row, col), series) for every pixel, row-major
+    pub fn pixel_series_iter(&self) -> impl Iterator<Item = ((usize, usize), TimeSeries)> + '_ {
+        self.pixel_series_iter_with_mask(self.mask())
+    }
+
+    pub fn pixel_series_iter_with_mask(
+        &self,
+        mask: Mask,
+    ) -> impl Iterator<Item = ((usize, usize), TimeSeries)> + '_ {
+        let nrows = self.nrows.max(0) as usize;
+        let ncols = self.ncols.max(0) as usize;
+        (0..nrows * ncols).filter_map(move |i| {
+            let (row, col) = (i / ncols, i % ncols);
+            self.pixel_series_with_mask(row, col, &mask)
+                .map(|series| ((row, col), series))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::otago;
+
+    #[test]
+    fn test_pixel_series() {
+        let mut data = otago();
+        let mut earlier = data.subset[0].clone();
+        earlier.modis_date = "A2024209".to_string();
+        earlier.calendar_date = "2024-07-27".to_string();
+        earlier.data[12] = 20;
+        data.subset.push(earlier);
+
+        let series = data.center_series().unwrap();
+        assert_eq!(series.len(), 2);
+        // Sorted by date, 254 is masked
+        assert_eq!(series.points[0].0.calendar_date(), "2024-07-27");
+        assert_eq!(series.points[0].1, Some(2.0));
+        assert_eq!(series.points[1].1, None);
+        assert_eq!(series.valid_count(), 1);
+
+        assert!(data.pixel_series(5, 0).is_none());
+        assert_eq!(data.pixel_series_iter().count(), 25);
+    }
+}
@@ -0,0 +1,343 @@
+// Land surface phenology metrics from a smoothed VI/LAI series (see timeseries::smooth)
+//
+// Seasons are found from the turning points of the whole series rather than per calendar
+// year, so several seasons per year and southern hemisphere seasons that cross 1 January
+// are handled the same way. As in MCD12Q2 a season belongs to the year of its peak.
+
+use chrono::Duration;
+use serde::Serialize;
+
+use crate::dates::ModisDate;
+use crate::timeseries::TimeSeries;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SeasonMethod {
+    /// Start/end where the curve crosses this fraction (0-1) of the left/right amplitude
+    Amplitude(f64),
+    /// Start/end at the steepest increase/decrease
+    Derivative,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PhenologyOptions {
+    pub method: SeasonMethod,
+    /// Smallest peak to base difference (in series units) counted as a season
+    pub min_amplitude: f64,
+}
+
+impl Default for PhenologyOptions {
+    fn default() -> Self {
+        Self {
+            method: SeasonMethod::Amplitude(0.2),
+            min_amplitude: 0.1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Season {
+    /// Year of the peak
+    pub year: i32,
+    pub start: ModisDate,
+    pub end: ModisDate,
+    pub peak: ModisDate,
+    pub peak_value: f64,
+    pub length_days: i64,
+    /// Mean of the minimum before and after the season
+    pub base_value: f64,
+    pub amplitude: f64,
+    /// Area between the curve and the base value from start to end (value x days)
+    pub small_integral: f64,
+    /// Area under the curve from start to end (value x days)
+    pub large_integral: f64,
+    /// MCD12Q2 equivalents, crossings of 15%, 50% and 90% of the amplitude
+    pub greenup: Option<ModisDate>,
+    pub mid_greenup: Option<ModisDate>,
+    pub maturity: Option<ModisDate>,
+    pub senescence: Option<ModisDate>,
+    pub mid_greendown: Option<ModisDate>,
+    pub dormancy: Option<ModisDate>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Turn {
+    Min(usize),
+    Max(usize),
+}
+
+impl Turn {
+    fn index(&self) -> usize {
+        match self {
+            Turn::Min(i) | Turn::Max(i) => *i,
+        }
+    }
+}
+
+// Alternating minima and maxima, starting and ending with a minimum
+fn turning_points(y: &[f64]) -> Vec<Turn> {
+    let mut turns = vec![Turn::Min(0)];
+    let mut rising = None;
+    for i in 1..y.len() {
+        if y[i] == y[i - 1] {
+            continue;
+        }
+        let up = y[i] > y[i - 1];
+        match (rising, up) {
+            (Some(true), false) => turns.push(Turn::Max(i - 1)),
+            (Some(false), true) => turns.push(Turn::Min(i - 1)),
+            _ => {}
+        }
+        rising = Some(up);
+    }
+    if rising == Some(true) {
+        turns.push(Turn::Max(y.len() - 1));
+    }
+    turns.push(Turn::Min(y.len() - 1));
+
+    // Tidy up so the list strictly alternates, keeping the more extreme point
+    let mut out: Vec<Turn> = Vec::new();
+    for turn in turns {
+        match (out.last().copied(), turn) {
+            (Some(Turn::Min(a)), Turn::Min(b)) => {
+                if y[b] < y[a] {
+                    *out.last_mut().unwrap() = Turn::Min(b);
+                }
+            }
+            (Some(Turn::Max(a)), Turn::Max(b)) => {
+                if y[b] > y[a] {
+                    *out.last_mut().unwrap() = Turn::Max(b);
+                }
+            }
+            _ => out.push(turn),
+        }
+    }
+    out
+}
+
+// Merge peaks smaller than min_amplitude into their neighbours
+fn significant_turns(y: &[f64], min_amplitude: f64) -> Vec<Turn> {
+    let mut turns = turning_points(y);
+    loop {
+        // (position of the max, its smaller side amplitude)
+        let weakest = turns
+            .iter()
+            .enumerate()
+            .filter_map(|(k, t)| match t {
+                Turn::Max(i) if k > 0 && k + 1 < turns.len() => {
+                    let left = y[*i] - y[turns[k - 1].index()];
+                    let right = y[*i] - y[turns[k + 1].index()];
+                    Some((k, left.min(right)))
+                }
+                _ => None,
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+
+        match weakest {
+            Some((k, amplitude)) if amplitude < min_amplitude => {
+                // Drop the max and the higher of its two minima
+                let (left, right) = (turns[k - 1].index(), turns[k + 1].index());
+                let drop_min = if y[left] > y[right] { k - 1 } else { k + 1 };
+                turns.remove(k.max(drop_min));
+                turns.remove(k.min(drop_min));
+            }
+            _ => return turns,
+        }
+    }
+}
+
+fn date_at(dates: &[ModisDate], t: f64) -> ModisDate {
+    ModisDate(dates[0].date() + Duration::days(t.round() as i64))
+}
+
+// First crossing of `threshold` going up between from and to (from < to)
+fn crossing_up(t: &[f64], y: &[f64], from: usize, to: usize, threshold: f64) -> Option<f64> {
+    (from + 1..=to).find_map(|i| {
+        if y[i] >= threshold && y[i - 1] < threshold {
+            Some(t[i - 1] + (threshold - y[i - 1]) / (y[i] - y[i - 1]) * (t[i] - t[i - 1]))
+        } else {
+            None
+        }
+    })
+}
+
+// Last crossing of `threshold` going down between from and to (from < to)
+fn crossing_down(t: &[f64], y: &[f64], from: usize, to: usize, threshold: f64) -> Option<f64> {
+    (from + 1..=to).rev().find_map(|i| {
+        if y[i - 1] >= threshold && y[i] < threshold {
+            Some(t[i - 1] + (y[i - 1] - threshold) / (y[i - 1] - y[i]) * (t[i] - t[i - 1]))
+        } else {
+            None
+        }
+    })
+}
+
+// Integral of y (minus base) between t0 and t1 with the trapezoid rule
+fn integral(t: &[f64], y: &[f64], t0: f64, t1: f64, base: f64) -> f64 {
+    let mut area = 0.0;
+    for i in 1..t.len() {
+        let (a, b) = (t[i - 1].max(t0), t[i].min(t1));
+        if b <= a {
+            continue;
+        }
+        let at = |x: f64| y[i - 1] + (y[i] - y[i - 1]) * (x - t[i - 1]) / (t[i] - t[i - 1]);
+        area += ((at(a) - base) + (at(b) - base)) / 2.0 * (b - a);
+    }
+    area
+}
+
+/// Phenology metrics for every complete season (bounded by an interior minimum on both sides)
+///
+/// Masked and non-finite points are skipped, the series is expected to be smoothed and gap
+/// filled.
+pub fn seasons(series: &TimeSeries, options: &PhenologyOptions) -> Vec<Season> {
+    let valid: Vec<(ModisDate, f64)> = series.valid().filter(|(_, v)| v.is_finite()).collect();
+    if valid.len() < 3 {
+        return Vec::new();
+    }
+    let dates: Vec<ModisDate> = valid.iter().map(|(d, _)| *d).collect();
+    let t: Vec<f64> = dates
+        .iter()
+        .map(|d| (d.date() - dates[0].date()).num_days() as f64)
+        .collect();
+    let y: Vec<f64> = valid.iter().map(|(_, v)| *v).collect();
+
+    let turns = significant_turns(&y, options.min_amplitude);
+    let mut seasons = Vec::new();
+
+    for k in 1..turns.len().saturating_sub(1) {
+        let (Turn::Min(left), Turn::Max(peak), Turn::Min(right)) =
+            (turns[k - 1], turns[k], turns[k + 1])
+        else {
+            continue;
+        };
+        // A series that starts or ends part way through a season has no true minimum there
+        if left == 0 || right == y.len() - 1 {
+            continue;
+        }
+
+        let left_base = y[left];
+        let right_base = y[right];
+        let peak_value = y[peak];
+        let up = |f: f64| crossing_up(&t, &y, left, peak, left_base + f * (peak_value - left_base));
+        let down = |f: f64| {
+            crossing_down(
+                &t,
+                &y,
+                peak,
+                right,
+                right_base + f * (peak_value - right_base),
+            )
+        };
+
+        let (start, end) = match options.method {
+            SeasonMethod::Amplitude(f) => (up(f), down(f)),
+            SeasonMethod::Derivative => {
+                let slope = |i: usize| (y[i] - y[i - 1]) / (t[i] - t[i - 1]);
+                let start = (left + 1..=peak)
+                    .max_by(|a, b| slope(*a).total_cmp(&slope(*b)))
+                    .map(|i| (t[i - 1] + t[i]) / 2.0);
+                let end = (peak + 1..=right)
+                    .min_by(|a, b| slope(*a).total_cmp(&slope(*b)))
+                    .map(|i| (t[i - 1] + t[i]) / 2.0);
+                (start, end)
+            }
+        };
+        let (Some(start), Some(end)) = (start, end) else {
+            continue;
+        };
+
+        let base_value = (left_base + right_base) / 2.0;
+        let peak_date = dates[peak];
+        let to_date = |t: Option<f64>| t.map(|t| date_at(&dates, t));
+
+        seasons.push(Season {
+            year: peak_date.year(),
+            start: date_at(&dates, start),
+            end: date_at(&dates, end),
+            peak: peak_date,
+            peak_value,
+            length_days: (end - start).round() as i64,
+            base_value,
+            amplitude: peak_value - base_value,
+            small_integral: integral(&t, &y, start, end, base_value),
+            large_integral: integral(&t, &y, start, end, 0.0),
+            greenup: to_date(up(0.15)),
+            mid_greenup: to_date(up(0.5)),
+            maturity: to_date(up(0.9)),
+            senescence: to_date(down(0.9)),
+            mid_greendown: to_date(down(0.5)),
+            dormancy: to_date(down(0.15)),
+        });
+    }
+    seasons
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 8-day series peaking at `peak_doy` every year, with `per_year` seasons
+    fn series(start: &str, n: usize, peak_doy: f64, per_year: f64) -> TimeSeries {
+        let start = ModisDate::parse(start).unwrap();
+        TimeSeries::new(
+            (0..n)
+                .map(|i| {
+                    let date = ModisDate(start.date() + Duration::days(i as i64 * 8));
+                    let phase =
+                        2.0 * std::f64::consts::PI * per_year * (date.doy() as f64 - peak_doy)
+                            / 365.0;
+                    (date, Some(0.5 + 0.3 * phase.cos()))
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_southern_hemisphere() {
+        // Otago style, peak mid January so each season starts the previous calendar year
+        let s = series("A2019182", 46 * 3, 15.0, 1.0);
+        let seasons = seasons(&s, &PhenologyOptions::default());
+        assert_eq!(seasons.len(), 2);
+        for season in &seasons {
+            assert_eq!(season.start.year(), season.year - 1);
+            assert!(season.peak.doy() < 30 || season.peak.doy() > 360);
+            assert!((season.amplitude - 0.6).abs() < 0.05);
+            assert!(season.small_integral > 0.0);
+            assert!(season.large_integral > season.small_integral);
+            assert!(season.greenup.unwrap() < season.mid_greenup.unwrap());
+            assert!(season.senescence.unwrap() < season.dormancy.unwrap());
+        }
+    }
+
+    #[test]
+    fn test_double_season() {
+        let s = series("A2020001", 46 * 2, 100.0, 2.0);
+        let double = seasons(&s, &PhenologyOptions::default());
+        assert_eq!(double.len(), 3);
+
+        let derivative = seasons(
+            &s,
+            &PhenologyOptions {
+                method: SeasonMethod::Derivative,
+                ..Default::default()
+            },
+        );
+        assert_eq!(derivative.len(), 3);
+        assert!(derivative[0].start < derivative[0].peak);
+
+        // NaN from an upstream division is skipped like a masked point
+        let mut with_nan = s.clone();
+        with_nan.points[30].1 = Some(f64::NAN);
+        assert_eq!(seasons(&with_nan, &PhenologyOptions::default()).len(), 3);
+
+        // Too small to count as seasons
+        let none = seasons(
+            &s,
+            &PhenologyOptions {
+                min_amplitude: 1.0,
+                ..Default::default()
+            },
+        );
+        assert!(none.is_empty());
+    }
+}
@@ -0,0 +1,444 @@
+// Smoothing and gap filling of vegetation index time series
+//
+// Every method takes optional per-point weights (aligned with `series.points`, e.g. from
+// `qc_weights`), masked points and points with weight <= 0 are gaps. The output is a regular
+// series from the first to the last date of the input, daily or at a composite interval.
+
+use chrono::Duration;
+
+use crate::dates::ModisDate;
+use crate::stats::weighted_least_squares;
+use crate::timeseries::TimeSeries;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interval {
+    Daily,
+    /// Every n days from the first date, e.g. 8 or 16 for composites
+    Days(u32),
+}
+
+impl Interval {
+    pub fn days(&self) -> i64 {
+        match self {
+            Interval::Daily => 1,
+            Interval::Days(n) => (*n).max(1) as i64,
+        }
+    }
+}
+
+/// Regular dates from `start` to `end` (inclusive)
+pub fn regular_dates(start: ModisDate, end: ModisDate, interval: Interval) -> Vec<ModisDate> {
+    let mut dates = Vec::new();
+    let mut date = start.date();
+    while date <= end.date() {
+        dates.push(ModisDate(date));
+        date += Duration::days(interval.days());
+    }
+    dates
+}
+
+/// Weights from a QC series of the same pixel, `weight` maps a raw QC value to a weight,
+/// dates without a QC value get 0
+pub fn qc_weights<F: Fn(i32) -> f64>(series: &TimeSeries, qc: &TimeSeries, weight: F) -> Vec<f64> {
+    series
+        .dates()
+        .map(|date| qc.get(date).map(|v| weight(v as i32)).unwrap_or(0.0))
+        .collect()
+}
+
+// Valid observations as (days since first date, value, weight)
+fn observations(series: &TimeSeries, weights: Option<&[f64]>) -> Vec<(f64, f64, f64)> {
+    let Some(start) = series.points.first().map(|(d, _)| d.date()) else {
+        return Vec::new();
+    };
+    series
+        .points
+        .iter()
+        .enumerate()
+        .filter_map(|(i, (date, value))| {
+            let w = weights
+                .map(|w| w.get(i).copied().unwrap_or(0.0))
+                .unwrap_or(1.0);
+            let v = (*value)?;
+            if w > 0.0 && v.is_finite() {
+                Some(((date.date() - start).num_days() as f64, v, w))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+// Output dates and their offsets in days from the first input date
+fn output_grid(series: &TimeSeries, interval: Interval) -> (Vec<ModisDate>, Vec<f64>) {
+    match (series.points.first(), series.points.last()) {
+        (Some((start, _)), Some((end, _))) => {
+            let dates = regular_dates(*start, *end, interval);
+            let t = dates
+                .iter()
+                .map(|d| (d.date() - start.date()).num_days() as f64)
+                .collect();
+            (dates, t)
+        }
+        _ => (Vec::new(), Vec::new()),
+    }
+}
+
+// Observations on the same day merged into their weighted mean
+fn merge_same_day(obs: Vec<(f64, f64, f64)>) -> Vec<(f64, f64, f64)> {
+    let mut merged: Vec<(f64, f64, f64)> = Vec::with_capacity(obs.len());
+    for (t, y, w) in obs {
+        match merged.last_mut() {
+            Some(last) if last.0 == t => {
+                last.1 = (last.1 * last.2 + y * w) / (last.2 + w);
+                last.2 += w;
+            }
+            _ => merged.push((t, y, w)),
+        }
+    }
+    merged
+}
+
+fn linear_at(obs: &[(f64, f64, f64)], t: f64) -> Option<f64> {
+    let first = obs.first()?;
+    let last = obs.last()?;
+    if t < first.0 || t > last.0 {
+        return None;
+    }
+    let i = obs.partition_point(|o| o.0 < t);
+    if i < obs.len() && obs[i].0 == t {
+        return Some(obs[i].1);
+    }
+    let (t0, y0, _) = obs[i - 1];
+    let (t1, y1, _) = obs[i];
+    Some(y0 + (y1 - y0) * (t - t0) / (t1 - t0))
+}
+
+/// Linear interpolation across gaps, no extrapolation beyond the first/last valid point
+pub fn linear(series: &TimeSeries, weights: Option<&[f64]>, interval: Interval) -> TimeSeries {
+    let obs = observations(series, weights);
+    let (dates, t) = output_grid(series, interval);
+    TimeSeries::new(
+        dates
+            .into_iter()
+            .zip(t)
+            .map(|(d, t)| (d, linear_at(&obs, t)))
+            .collect(),
+    )
+}
+
+/// Natural cubic spline through the valid points, no extrapolation
+///
+/// Points on the same date are replaced by their weighted mean.
+pub fn spline(series: &TimeSeries, weights: Option<&[f64]>, interval: Interval) -> TimeSeries {
+    let obs = merge_same_day(observations(series, weights));
+    let n = obs.len();
+    if n < 3 {
+        return linear(series, weights, interval);
+    }
+
+    // Second derivatives from the tridiagonal system, natural boundary conditions
+    let h: Vec<f64> = (0..n - 1).map(|i| obs[i + 1].0 - obs[i].0).collect();
+    let mut m = vec![0.0; n];
+    let mut c = vec![0.0; n];
+    let mut d = vec![0.0; n];
+    for i in 1..n - 1 {
+        let a = h[i - 1];
+        let b = 2.0 * (h[i - 1] + h[i]);
+        let r = 6.0 * ((obs[i + 1].1 - obs[i].1) / h[i] - (obs[i].1 - obs[i - 1].1) / h[i - 1]);
+        let denom = b - a * c[i - 1];
+        c[i] = h[i] / denom;
+        d[i] = (r - a * d[i - 1]) / denom;
+    }
+    for i in (1..n - 1).rev() {
+        m[i] = d[i] - c[i] * m[i + 1];
+    }
+
+    let (dates, t) = output_grid(series, interval);
+    let values = t.into_iter().map(|t| {
+        if t < obs[0].0 || t > obs[n - 1].0 {
+            return None;
+        }
+        let i = obs.partition_point(|o| o.0 <= t).clamp(1, n - 1) - 1;
+        let (t0, y0, _) = obs[i];
+        let (t1, y1, _) = obs[i + 1];
+        let h = t1 - t0;
+        let a = (t1 - t) / h;
+        let b = (t - t0) / h;
+        Some(a * y0 + b * y1 + ((a.powi(3) - a) * m[i] + (b.powi(3) - b) * m[i + 1]) * h * h / 6.0)
+    });
+    TimeSeries::new(dates.into_iter().zip(values).collect())
+}
+
+/// Whittaker smoother (Eilers 2003) with second order differences
+///
+/// Observations are placed on the output grid, `lambda` controls smoothness and depends on the
+/// interval (roughly 1e3-1e4 daily, 10-100 for 8-day composites).
+pub fn whittaker(
+    series: &TimeSeries,
+    weights: Option<&[f64]>,
+    lambda: f64,
+    interval: Interval,
+) -> TimeSeries {
+    let obs = observations(series, weights);
+    let (dates, _) = output_grid(series, interval);
+    let n = dates.len();
+    if obs.is_empty() || n == 0 {
+        return TimeSeries::new(dates.into_iter().map(|d| (d, None)).collect());
+    }
+
+    // Weighted average of the observations falling on each grid cell
+    let step = interval.days() as f64;
+    let mut w = vec![0.0; n];
+    let mut wy = vec![0.0; n];
+    for (t, y, weight) in &obs {
+        let i = ((t / step).round() as usize).min(n - 1);
+        w[i] += weight;
+        wy[i] += weight * y;
+    }
+
+    // (W + lambda D'D) z = W y, D'D is pentadiagonal
+    let mut a0: Vec<f64> = w.iter().map(|w| w + 1e-9).collect();
+    let mut a1 = vec![0.0; n];
+    let mut a2 = vec![0.0; n];
+    let coef = [1.0, -2.0, 1.0];
+    for j in 0..n.saturating_sub(2) {
+        for p in 0..3 {
+            a0[j + p] += lambda * coef[p] * coef[p];
+            if p < 2 {
+                a1[j + p] += lambda * coef[p] * coef[p + 1];
+            }
+        }
+        a2[j] += lambda * coef[0] * coef[2];
+    }
+
+    // Banded Cholesky, l1[i] = L[i][i-1], l2[i] = L[i][i-2]
+    let mut l0 = vec![0.0; n];
+    let mut l1 = vec![0.0; n];
+    let mut l2 = vec![0.0; n];
+    for i in 0..n {
+        if i >= 2 {
+            l2[i] = a2[i - 2] / l0[i - 2];
+        }
+        if i >= 1 {
+            l1[i] = (a1[i - 1] - l2[i] * l1[i - 1]) / l0[i - 1];
+        }
+        l0[i] = (a0[i] - l1[i] * l1[i] - l2[i] * l2[i]).max(1e-12).sqrt();
+    }
+    let mut z = vec![0.0; n];
+    for i in 0..n {
+        let mut s = wy[i];
+        if i >= 1 {
+            s -= l1[i] * z[i - 1];
+        }
+        if i >= 2 {
+            s -= l2[i] * z[i - 2];
+        }
+        z[i] = s / l0[i];
+    }
+    for i in (0..n).rev() {
+        let mut s = z[i];
+        if i + 1 < n {
+            s -= l1[i + 1] * z[i + 1];
+        }
+        if i + 2 < n {
+            s -= l2[i + 2] * z[i + 2];
+        }
+        z[i] = s / l0[i];
+    }
+
+    TimeSeries::new(dates.into_iter().zip(z.into_iter().map(Some)).collect())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SavitzkyGolay {
+    /// Observations either side of each point in the local fit
+    pub half_window: usize,
+    pub degree: usize,
+    /// TIMESAT style upper envelope iterations, 0 for a plain fit
+    pub envelope_iterations: usize,
+}
+
+impl Default for SavitzkyGolay {
+    fn default() -> Self {
+        Self {
+            half_window: 4,
+            degree: 2,
+            envelope_iterations: 2,
+        }
+    }
+}
+
+fn savitzky_golay_pass(obs: &[(f64, f64, f64)], sg: &SavitzkyGolay) -> Vec<f64> {
+    (0..obs.len())
+        .map(|i| {
+            let lo = i.saturating_sub(sg.half_window);
+            let hi = (i + sg.half_window + 1).min(obs.len());
+            let window = &obs[lo..hi];
+            let degree = sg.degree.min(window.len().saturating_sub(1));
+            let t0 = obs[i].0;
+            let rows: Vec<Vec<f64>> = window
+                .iter()
+                .map(|(t, _, _)| (0..=degree).map(|p| (t - t0).powi(p as i32)).collect())
+                .collect();
+            let y: Vec<f64> = window.iter().map(|o| o.1).collect();
+            let w: Vec<f64> = window.iter().map(|o| o.2).collect();
+            // Evaluated at t0 the fit is the constant term
+            weighted_least_squares(&rows, &y, &w)
+                .map(|c| c[0])
+                .unwrap_or(obs[i].1)
+        })
+        .collect()
+}
+
+/// Weighted Savitzky-Golay filter over the valid observations, then linearly interpolated
+/// to the output grid
+///
+/// With `envelope_iterations` the observations below the fit are raised to it and the fit is
+/// repeated, adapting to the upper envelope as in TIMESAT (clouds and snow only lower VIs).
+pub fn savitzky_golay(
+    series: &TimeSeries,
+    weights: Option<&[f64]>,
+    sg: &SavitzkyGolay,
+    interval: Interval,
+) -> TimeSeries {
+    let mut obs = observations(series, weights);
+    let mut fit = savitzky_golay_pass(&obs, sg);
+    for _ in 0..sg.envelope_iterations {
+        for (o, f) in obs.iter_mut().zip(&fit) {
+            o.1 = o.1.max(*f);
+        }
+        fit = savitzky_golay_pass(&obs, sg);
+    }
+    let fitted: Vec<(f64, f64, f64)> = obs.iter().zip(fit).map(|(o, f)| (o.0, f, o.2)).collect();
+
+    let (dates, t) = output_grid(series, interval);
+    TimeSeries::new(
+        dates
+            .into_iter()
+            .zip(t)
+            .map(|(d, t)| (d, linear_at(&fitted, t)))
+            .collect(),
+    )
+}
+
+/// Weighted harmonic (Fourier) fit, `harmonics` terms with base period `period_days`
+/// (365.25 for an annual cycle)
+pub fn harmonic(
+    series: &TimeSeries,
+    weights: Option<&[f64]>,
+    harmonics: usize,
+    period_days: f64,
+    interval: Interval,
+) -> TimeSeries {
+    let obs = observations(series, weights);
+    let basis = |t: f64| -> Vec<f64> {
+        let mut row = vec![1.0];
+        for k in 1..=harmonics {
+            let a = 2.0 * std::f64::consts::PI * k as f64 * t / period_days;
+            row.push(a.cos());
+            row.push(a.sin());
+        }
+        row
+    };
+    let rows: Vec<Vec<f64>> = obs.iter().map(|o| basis(o.0)).collect();
+    let y: Vec<f64> = obs.iter().map(|o| o.1).collect();
+    let w: Vec<f64> = obs.iter().map(|o| o.2).collect();
+    let coefficients = weighted_least_squares(&rows, &y, &w);
+
+    let (dates, t) = output_grid(series, interval);
+    TimeSeries::new(
+        dates
+            .into_iter()
+            .zip(t)
+            .map(|(d, t)| {
+                let value = coefficients
+                    .as_ref()
+                    .map(|c| basis(t).iter().zip(c).map(|(b, c)| b * c).sum());
+                (d, value)
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn series(values: &[Option<f64>]) -> TimeSeries {
+        let start = ModisDate::parse("A2020001").unwrap();
+        TimeSeries::new(
+            values
+                .iter()
+                .enumerate()
+                .map(|(i, v)| (ModisDate(start.date() + Duration::days(i as i64 * 8)), *v))
+                .collect(),
+        )
+    }
+
+    fn seasonal(n: usize) -> Vec<Option<f64>> {
+        (0..n)
+            .map(|i| Some(0.5 + 0.3 * (2.0 * std::f64::consts::PI * i as f64 * 8.0 / 365.25).sin()))
+            .collect()
+    }
+
+    #[test]
+    fn test_linear_and_spline() {
+        let s = series(&[Some(0.0), None, Some(2.0), Some(3.0)]);
+        let daily = linear(&s, None, Interval::Daily);
+        assert_eq!(daily.len(), 25);
+        assert_eq!(daily.points[8].1, Some(1.0));
+
+        let splined = spline(&s, None, Interval::Days(8));
+        assert_eq!(splined.points[0].1, Some(0.0));
+        assert!((splined.points[2].1.unwrap() - 2.0).abs() < 1e-9);
+
+        // Two observations on one date are averaged rather than dividing by a zero interval
+        let mut points = series(&[Some(0.0), Some(1.0), Some(2.0), Some(3.0)]).points;
+        points.push((points[1].0, Some(3.0)));
+        let splined = spline(&TimeSeries::new(points), None, Interval::Days(8));
+        assert_eq!(splined.points[1].1, Some(2.0));
+        assert!(splined.points.iter().all(|(_, v)| v.unwrap().is_finite()));
+    }
+
+    #[test]
+    fn test_whittaker() {
+        let mut values = seasonal(46);
+        values[10] = Some(0.0);
+        let s = series(&values);
+        let mut weights = vec![1.0; 46];
+        weights[10] = 0.0;
+        let smoothed = whittaker(&s, Some(&weights), 10.0, Interval::Days(8));
+        assert_eq!(smoothed.len(), 46);
+        // The zero weighted dip is filled from its neighbours
+        let expected = seasonal(46)[10].unwrap();
+        assert!((smoothed.points[10].1.unwrap() - expected).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_savitzky_golay_envelope() {
+        let mut values = seasonal(46);
+        values[20] = Some(0.1);
+        let s = series(&values);
+        let plain = savitzky_golay(
+            &s,
+            None,
+            &SavitzkyGolay {
+                envelope_iterations: 0,
+                ..Default::default()
+            },
+            Interval::Days(8),
+        );
+        let envelope = savitzky_golay(&s, None, &SavitzkyGolay::default(), Interval::Days(8));
+        assert!(envelope.points[20].1.unwrap() > plain.points[20].1.unwrap());
+    }
+
+    #[test]
+    fn test_harmonic() {
+        let s = series(&seasonal(92));
+        let fitted = harmonic(&s, None, 2, 365.25, Interval::Days(8));
+        for ((_, fit), (_, value)) in fitted.iter().zip(s.iter()) {
+            assert!((fit.unwrap() - value.unwrap()).abs() < 1e-6);
+        }
+    }
+}
@@ -0,0 +1,515 @@
+// Trend tests and breakpoint detection for long pixel time series
+// Slopes are in series units per year
+
+use serde::Serialize;
+
+use crate::dates::ModisDate;
+use crate::stats::percentile;
+use crate::structs::ModisData;
+use crate::timeseries::climatology::{composite_slot, Climatology};
+use crate::timeseries::TimeSeries;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct MannKendall {
+    pub s: f64,
+    pub var_s: f64,
+    pub z: f64,
+    /// Two sided p-value
+    pub p_value: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct LinearTrend {
+    pub slope: f64,
+    /// Value at the first date
+    pub intercept: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Segment {
+    pub start: ModisDate,
+    pub end: ModisDate,
+    pub trend: LinearTrend,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Breakpoints {
+    /// First date of each new segment
+    pub breaks: Vec<ModisDate>,
+    pub segments: Vec<Segment>,
+    /// Jump in the fitted trend at each break
+    pub magnitudes: Vec<f64>,
+}
+
+fn normal_cdf(x: f64) -> f64 {
+    // Abramowitz and Stegun 7.1.26
+    let z = x.abs() / std::f64::consts::SQRT_2;
+    let t = 1.0 / (1.0 + 0.3275911 * z);
+    let erf = 1.0
+        - (((((1.061405429 * t - 1.453152027) * t) + 1.421413741) * t - 0.284496736) * t
+            + 0.254829592)
+            * t
+            * (-z * z).exp();
+    if x >= 0.0 {
+        0.5 * (1.0 + erf)
+    } else {
+        0.5 * (1.0 - erf)
+    }
+}
+
+// Valid values that are finite, a NaN or infinity would poison every statistic below
+fn finite(series: &TimeSeries) -> impl Iterator<Item = (ModisDate, f64)> + '_ {
+    series.valid().filter(|(_, v)| v.is_finite())
+}
+
+// S and its variance (with tie correction) for values in time order
+fn mann_kendall_s(values: &[f64]) -> (f64, f64) {
+    let n = values.len();
+    let mut s = 0.0;
+    for i in 0..n {
+        for j in i + 1..n {
+            s += (values[j] - values[i]).signum() * ((values[j] != values[i]) as i32 as f64);
+        }
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let mut ties = 0.0;
+    let mut i = 0;
+    while i < n {
+        let mut j = i;
+        while j + 1 < n && sorted[j + 1] == sorted[i] {
+            j += 1;
+        }
+        let t = (j - i + 1) as f64;
+        ties += t * (t - 1.0) * (2.0 * t + 5.0);
+        i = j + 1;
+    }
+    let n = n as f64;
+    (s, (n * (n - 1.0) * (2.0 * n + 5.0) - ties) / 18.0)
+}
+
+fn mann_kendall_from(s: f64, var_s: f64) -> MannKendall {
+    let z = if var_s <= 0.0 {
+        0.0
+    } else if s > 0.0 {
+        (s - 1.0) / var_s.sqrt()
+    } else if s < 0.0 {
+        (s + 1.0) / var_s.sqrt()
+    } else {
+        0.0
+    };
+    MannKendall {
+        s,
+        var_s,
+        z,
+        p_value: 2.0 * (1.0 - normal_cdf(z.abs())),
+    }
+}
+
+/// Mann-Kendall test on the valid values
+pub fn mann_kendall(series: &TimeSeries) -> MannKendall {
+    let values: Vec<f64> = finite(series).map(|(_, v)| v).collect();
+    let (s, var_s) = mann_kendall_s(&values);
+    mann_kendall_from(s, var_s)
+}
+
+/// Seasonal Mann-Kendall (Hirsch et al. 1982), S and its variance summed over composite slots
+pub fn seasonal_mann_kendall(series: &TimeSeries, composite_days: u32) -> MannKendall {
+    let (mut s, mut var_s) = (0.0, 0.0);
+    for values in by_slot(series, composite_days) {
+        let (ss, vs) = mann_kendall_s(&values.iter().map(|(_, v)| *v).collect::<Vec<_>>());
+        s += ss;
+        var_s += vs;
+    }
+    mann_kendall_from(s, var_s)
+}
+
+// Valid (years since first date, value) grouped by composite slot
+fn by_slot(series: &TimeSeries, composite_days: u32) -> Vec<Vec<(f64, f64)>> {
+    let mut slots: std::collections::BTreeMap<u32, Vec<(f64, f64)>> = Default::default();
+    let Some(start) = series.points.first().map(|(d, _)| *d) else {
+        return Vec::new();
+    };
+    for (date, value) in finite(series) {
+        slots
+            .entry(composite_slot(date, composite_days))
+            .or_default()
+            .push((years_between(start, date), value));
+    }
+    slots.into_values().collect()
+}
+
+fn years_between(start: ModisDate, date: ModisDate) -> f64 {
+    (date.date() - start.date()).num_days() as f64 / 365.25
+}
+
+fn valid_points(series: &TimeSeries) -> Vec<(f64, f64)> {
+    let Some(start) = series.points.first().map(|(d, _)| *d) else {
+        return Vec::new();
+    };
+    finite(series)
+        .map(|(date, value)| (years_between(start, date), value))
+        .collect()
+}
+
+fn median(mut values: Vec<f64>) -> f64 {
+    values.sort_by(f64::total_cmp);
+    percentile(&values, 50.0)
+}
+
+fn theil_sen_points(points: &[(f64, f64)], slopes: Vec<f64>) -> Option<LinearTrend> {
+    if slopes.is_empty() {
+        return None;
+    }
+    let slope = median(slopes);
+    let intercept = median(points.iter().map(|(t, y)| y - slope * t).collect());
+    Some(LinearTrend { slope, intercept })
+}
+
+/// Sen's slope with a median intercept (Theil-Sen robust line)
+pub fn sens_slope(series: &TimeSeries) -> Option<LinearTrend> {
+    let points = valid_points(series);
+    let mut slopes = Vec::new();
+    for i in 0..points.len() {
+        for j in i + 1..points.len() {
+            if points[j].0 > points[i].0 {
+                slopes.push((points[j].1 - points[i].1) / (points[j].0 - points[i].0));
+            }
+        }
+    }
+    theil_sen_points(&points, slopes)
+}
+
+/// Seasonal Sen's slope, the median of slopes between the same composite in different years
+pub fn seasonal_sens_slope(series: &TimeSeries, composite_days: u32) -> Option<LinearTrend> {
+    let mut slopes = Vec::new();
+    for values in by_slot(series, composite_days) {
+        for i in 0..values.len() {
+            for j in i + 1..values.len() {
+                if values[j].0 > values[i].0 {
+                    slopes.push((values[j].1 - values[i].1) / (values[j].0 - values[i].0));
+                }
+            }
+        }
+    }
+    theil_sen_points(&valid_points(series), slopes)
+}
+
+fn ols_points(points: &[(f64, f64)]) -> Option<LinearTrend> {
+    let n = points.len() as f64;
+    if points.len() < 2 {
+        return None;
+    }
+    let mt = points.iter().map(|p| p.0).sum::<f64>() / n;
+    let my = points.iter().map(|p| p.1).sum::<f64>() / n;
+    let sxx: f64 = points.iter().map(|p| (p.0 - mt).powi(2)).sum();
+    if sxx == 0.0 {
+        return None;
+    }
+    let sxy: f64 = points.iter().map(|p| (p.0 - mt) * (p.1 - my)).sum();
+    let slope = sxy / sxx;
+    Some(LinearTrend {
+        slope,
+        intercept: my - slope * mt,
+    })
+}
+
+/// Ordinary least squares line
+pub fn ols(series: &TimeSeries) -> Option<LinearTrend> {
+    ols_points(&valid_points(series))
+}
+
+/// Subtract the mean of each composite slot, leaving trend and noise
+pub fn deseasonalise(series: &TimeSeries, composite_days: u32) -> TimeSeries {
+    let climatology = Climatology::from_series(series, composite_days, &[], None);
+    series
+        .iter()
+        .map(|(date, value)| {
+            let mean = climatology.normal(*date).and_then(|n| n.mean);
+            (*date, value.zip(mean).map(|(v, m)| v - m))
+        })
+        .collect()
+}
+
+// Prefix sums for O(1) residual sum of squares of a linear fit on a range
+struct Rss {
+    t: Vec<f64>,
+    y: Vec<f64>,
+    tt: Vec<f64>,
+    ty: Vec<f64>,
+    yy: Vec<f64>,
+}
+
+impl Rss {
+    fn new(points: &[(f64, f64)]) -> Rss {
+        let mut rss = Rss {
+            t: vec![0.0],
+            y: vec![0.0],
+            tt: vec![0.0],
+            ty: vec![0.0],
+            yy: vec![0.0],
+        };
+        for (t, y) in points {
+            rss.t.push(rss.t.last().unwrap() + t);
+            rss.y.push(rss.y.last().unwrap() + y);
+            rss.tt.push(rss.tt.last().unwrap() + t * t);
+            rss.ty.push(rss.ty.last().unwrap() + t * y);
+            rss.yy.push(rss.yy.last().unwrap() + y * y);
+        }
+        rss
+    }
+
+    // Points i..j (exclusive)
+    fn cost(&self, i: usize, j: usize) -> f64 {
+        let n = (j - i) as f64;
+        let st = self.t[j] - self.t[i];
+        let sy = self.y[j] - self.y[i];
+        let stt = self.tt[j] - self.tt[i];
+        let sty = self.ty[j] - self.ty[i];
+        let syy = self.yy[j] - self.yy[i];
+        let sxx = stt - st * st / n;
+        let syy_c = syy - sy * sy / n;
+        if sxx <= 0.0 {
+            return syy_c.max(0.0);
+        }
+        let sxy = sty - st * sy / n;
+        (syy_c - sxy * sxy / sxx).max(0.0)
+    }
+}
+
+/// BFAST-lite style breakpoints: the deseasonalised series is split into linear segments
+/// by dynamic programming (Bai and Perron), the number of breaks is chosen by BIC
+///
+/// `min_segment` is the minimum number of observations per segment.
+pub fn breakpoints(
+    series: &TimeSeries,
+    composite_days: u32,
+    max_breaks: usize,
+    min_segment: usize,
+) -> Breakpoints {
+    let deseasonalised = deseasonalise(series, composite_days);
+    let dates: Vec<ModisDate> = finite(&deseasonalised).map(|(d, _)| d).collect();
+    let points = valid_points(&deseasonalised);
+    let n = points.len();
+    let h = min_segment.max(3);
+    let rss = Rss::new(&points);
+
+    // best[m][j]: lowest cost of splitting points 0..j into m + 1 segments, with the split
+    let mut best: Vec<Vec<(f64, usize)>> = vec![(0..=n)
+        .map(|j| {
+            if j >= h {
+                (rss.cost(0, j), 0)
+            } else {
+                (f64::INFINITY, 0)
+            }
+        })
+        .collect()];
+    for m in 1..=max_breaks {
+        let previous = &best[m - 1];
+        let row = (0..=n)
+            .map(|j| {
+                if j < (m + 1) * h {
+                    return (f64::INFINITY, 0);
+                }
+                (m * h..=j - h)
+                    .map(|i| (previous[i].0 + rss.cost(i, j), i))
+                    .min_by(|a, b| a.0.total_cmp(&b.0))
+                    .unwrap_or((f64::INFINITY, 0))
+            })
+            .collect();
+        best.push(row);
+    }
+
+    let bic = |m: usize| {
+        let r = best[m].get(n).map(|b| b.0).unwrap_or(f64::INFINITY);
+        let k = (m + 1) * 2 + m;
+        n as f64 * (r.max(1e-12) / n as f64).ln() + k as f64 * (n as f64).ln()
+    };
+    let m = (0..best.len())
+        .filter(|m| best[*m].get(n).map(|b| b.0.is_finite()).unwrap_or(false))
+        .min_by(|a, b| bic(*a).total_cmp(&bic(*b)));
+
+    let Some(m) = m else {
+        return Breakpoints {
+            breaks: Vec::new(),
+            segments: Vec::new(),
+            magnitudes: Vec::new(),
+        };
+    };
+
+    let mut bounds = vec![n];
+    let mut j = n;
+    for k in (1..=m).rev() {
+        j = best[k][j].1;
+        bounds.push(j);
+    }
+    bounds.push(0);
+    bounds.reverse();
+
+    let segments: Vec<Segment> = bounds
+        .windows(2)
+        .filter_map(|w| {
+            let trend = ols_points(&points[w[0]..w[1]])?;
+            Some(Segment {
+                start: dates[w[0]],
+                end: dates[w[1] - 1],
+                trend,
+            })
+        })
+        .collect();
+
+    let magnitudes = bounds[1..bounds.len() - 1]
+        .iter()
+        .zip(segments.windows(2))
+        .map(|(b, s)| {
+            let t = points[*b].0;
+            (s[1].trend.intercept + s[1].trend.slope * t)
+                - (s[0].trend.intercept + s[0].trend.slope * t)
+        })
+        .collect();
+
+    Breakpoints {
+        breaks: bounds[1..bounds.len() - 1]
+            .iter()
+            .map(|b| dates[*b])
+            .collect(),
+        segments,
+        magnitudes,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PixelTrend {
+    pub row: usize,
+    pub col: usize,
+    pub mann_kendall: MannKendall,
+    pub sens_slope: Option<LinearTrend>,
+    pub ols: Option<LinearTrend>,
+    pub breakpoints: Breakpoints,
+}
+
+impl ModisData {
+    /// Seasonal Mann-Kendall, seasonal Sen's slope, OLS and breakpoints for every pixel
+    /// (row-major), `composite_days` defaults to the product's composite period
+    pub fn trends(
+        &self,
+        composite_days: Option<u32>,
+        max_breaks: usize,
+        min_segment: usize,
+    ) -> Result<Vec<PixelTrend>, Box<dyn std::error::Error>> {
+        let composite_days = composite_days
+            .or(self.composite_days())
+            .ok_or("unknown composite period, pass composite_days")?;
+        Ok(self
+            .pixel_series_iter()
+            .map(|((row, col), series)| PixelTrend {
+                row,
+                col,
+                mann_kendall: seasonal_mann_kendall(&series, composite_days),
+                sens_slope: seasonal_sens_slope(&series, composite_days),
+                ols: ols(&deseasonalise(&series, composite_days)),
+                breakpoints: breakpoints(&series, composite_days, max_breaks, min_segment),
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structs::Subset;
+    use crate::tests::otago;
+    use chrono::Duration;
+
+    // Ten years of 16-day composites with a seasonal cycle, `slope` per year and a step
+    fn series(slope: f64, step_year: Option<i32>) -> TimeSeries {
+        let mut points = Vec::new();
+        for year in 2010..2020 {
+            let start = ModisDate::from_year_doy(year, 1).unwrap();
+            for k in 0..23 {
+                let date = ModisDate(start.date() + Duration::days(k * 16));
+                let season = 0.2 * (2.0 * std::f64::consts::PI * k as f64 / 23.0).sin();
+                let noise = 0.005 * ((k * 7 + year as i64) % 5) as f64;
+                let step = match step_year {
+                    Some(y) if year >= y => -0.3,
+                    _ => 0.0,
+                };
+                let value = 0.5 + season + slope * (year - 2010) as f64 + noise + step;
+                points.push((date, Some(value)));
+            }
+        }
+        TimeSeries::new(points)
+    }
+
+    #[test]
+    fn test_trends() {
+        let greening = series(0.01, None);
+        let mk = seasonal_mann_kendall(&greening, 16);
+        assert!(mk.z > 3.0);
+        assert!(mk.p_value < 0.01);
+        let sen = seasonal_sens_slope(&greening, 16).unwrap();
+        assert!((sen.slope - 0.01).abs() < 0.002);
+
+        let flat = series(0.0, None);
+        assert!(seasonal_mann_kendall(&flat, 16).p_value > 0.05);
+
+        let ols = ols(&deseasonalise(&greening, 16)).unwrap();
+        assert!((ols.slope - 0.01).abs() < 0.002);
+    }
+
+    #[test]
+    fn test_nan_observation() {
+        let mut greening = series(0.01, None);
+        greening.points[40].1 = Some(f64::NAN);
+        greening.points[41].1 = Some(f64::INFINITY);
+        assert!(seasonal_mann_kendall(&greening, 16).p_value < 0.01);
+        assert!(mann_kendall(&greening).z.is_finite());
+        let sen = sens_slope(&greening).unwrap();
+        assert!(sen.slope.is_finite());
+        assert!((seasonal_sens_slope(&greening, 16).unwrap().slope - 0.01).abs() < 0.002);
+        assert!(breakpoints(&greening, 16, 3, 23).breaks.is_empty());
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let burnt = series(0.0, Some(2015));
+        let result = breakpoints(&burnt, 16, 3, 23);
+        assert_eq!(result.breaks.len(), 1);
+        assert_eq!(result.breaks[0].year(), 2015);
+        assert!((result.magnitudes[0] + 0.3).abs() < 0.05);
+
+        let none = breakpoints(&series(0.0, None), 16, 3, 23);
+        assert!(none.breaks.is_empty());
+    }
+
+    #[test]
+    fn test_pixel_trends() {
+        // 8-day LAI greening by 0.2 per year, ending mid-season so the cycle would bias a raw fit
+        let mut data = otago();
+        let template = data.subset[0].clone();
+        data.subset.clear();
+        for year in 2010..2015 {
+            for k in 0..if year == 2014 { 12 } else { 46 } {
+                let date = ModisDate::from_year_doy(year, 1 + 8 * k).unwrap();
+                let season = 30.0 * (2.0 * std::f64::consts::PI * k as f64 / 46.0).sin();
+                let value = (40.0 + season + 2.0 * (year - 2010) as f64).round() as i32;
+                data.subset.push(Subset {
+                    modis_date: date.to_string(),
+                    calendar_date: date.calendar_date(),
+                    data: vec![value; 25],
+                    ..template.clone()
+                });
+            }
+        }
+
+        let trends = data.trends(None, 2, 46).unwrap();
+        assert_eq!(trends.len(), 25);
+        let pixel = &trends[12];
+        assert_eq!((pixel.row, pixel.col), (2, 2));
+        assert!(pixel.mann_kendall.p_value < 0.01);
+        assert!((pixel.sens_slope.unwrap().slope - 0.2).abs() < 0.02);
+        assert!((pixel.ols.unwrap().slope - 0.2).abs() < 0.02);
+        let raw = ols(&data.pixel_series(2, 2).unwrap()).unwrap();
+        assert!((raw.slope - 0.2).abs() > 0.1);
+    }
+}
@@ -0,0 +1,117 @@
+// Universal Transverse Mercator on the WGS84 ellipsoid
+// Krüger series to third order in n, good to about a millimetre within a zone
+// https://en.wikipedia.org/wiki/Universal_Transverse_Mercator_coordinate_system
+
+const A: f64 = 6_378_137.0;
+const F: f64 = 1.0 / 298.257_223_563;
+const K0: f64 = 0.9996;
+const FALSE_EASTING: f64 = 500_000.0;
+const FALSE_NORTHING_SOUTH: f64 = 10_000_000.0;
+
+struct Series {
+    // Rectifying radius
+    a: f64,
+    alpha: [f64; 3],
+    beta: [f64; 3],
+    delta: [f64; 3],
+    n: f64,
+}
+
+fn series() -> Series {
+    let n = F / (2.0 - F);
+    let (n2, n3) = (n * n, n * n * n);
+    Series {
+        a: A / (1.0 + n) * (1.0 + n2 / 4.0 + n2 * n2 / 64.0),
+        alpha: [
+            n / 2.0 - 2.0 * n2 / 3.0 + 5.0 * n3 / 16.0,
+            13.0 * n2 / 48.0 - 3.0 * n3 / 5.0,
+            61.0 * n3 / 240.0,
+        ],
+        beta: [
+            n / 2.0 - 2.0 * n2 / 3.0 + 37.0 * n3 / 96.0,
+            n2 / 48.0 + n3 / 15.0,
+            17.0 * n3 / 480.0,
+        ],
+        delta: [
+            2.0 * n - 2.0 * n2 / 3.0 - 2.0 * n3,
+            7.0 * n2 / 3.0 - 8.0 * n3 / 5.0,
+            56.0 * n3 / 15.0,
+        ],
+        n,
+    }
+}
+
+/// Zone (1-60) containing a longitude
+pub fn zone(longitude: f64) -> u8 {
+    (((longitude + 180.0) / 6.0).floor() as i64).rem_euclid(60) as u8 + 1
+}
+
+/// Central meridian of a zone in degrees
+pub fn central_meridian(zone: u8) -> f64 {
+    zone as f64 * 6.0 - 183.0
+}
+
+/// Project latitude/longitude (degrees) to easting/northing (metres) in `zone`
+pub fn to_utm(latitude: f64, longitude: f64, zone: u8, north: bool) -> (f64, f64) {
+    let s = series();
+    let lat = latitude.to_radians();
+    let lon = (longitude - central_meridian(zone)).to_radians();
+    let c = 2.0 * s.n.sqrt() / (1.0 + s.n);
+    let t = (lat.sin().atanh() - c * (c * lat.sin()).atanh()).sinh();
+    let xi = (t / lon.cos()).atan();
+    let eta = (lon.sin() / (1.0 + t * t).sqrt()).atanh();
+
+    let (mut e, mut n) = (eta, xi);
+    for (j, alpha) in s.alpha.iter().enumerate() {
+        let k = 2.0 * (j + 1) as f64;
+        e += alpha * (k * xi).cos() * (k * eta).sinh();
+        n += alpha * (k * xi).sin() * (k * eta).cosh();
+    }
+    let false_northing = if north { 0.0 } else { FALSE_NORTHING_SOUTH };
+    (FALSE_EASTING + K0 * s.a * e, false_northing + K0 * s.a * n)
+}
+
+/// Inverse of [`to_utm`], returns (latitude, longitude) in degrees
+pub fn from_utm(easting: f64, northing: f64, zone: u8, north: bool) -> (f64, f64) {
+    let s = series();
+    let false_northing = if north { 0.0 } else { FALSE_NORTHING_SOUTH };
+    let xi = (northing - false_northing) / (K0 * s.a);
+    let eta = (easting - FALSE_EASTING) / (K0 * s.a);
+
+    let (mut xi1, mut eta1) = (xi, eta);
+    for (j, beta) in s.beta.iter().enumerate() {
+        let k = 2.0 * (j + 1) as f64;
+        xi1 -= beta * (k * xi).sin() * (k * eta).cosh();
+        eta1 -= beta * (k * xi).cos() * (k * eta).sinh();
+    }
+    let chi = (xi1.sin() / eta1.cosh()).asin();
+    let mut lat = chi;
+    for (j, delta) in s.delta.iter().enumerate() {
+        lat += delta * (2.0 * (j + 1) as f64 * chi).sin();
+    }
+    let lon = (eta1.sinh() / xi1.cos()).atan();
+    (lat.to_degrees(), central_meridian(zone) + lon.to_degrees())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_utm() {
+        // On the central meridian the northing is the scaled meridian arc
+        let (e, n) = to_utm(45.0, 9.0, 32, true);
+        assert!((e - 500_000.0).abs() < 1e-6);
+        assert!((n - 4_982_950.4).abs() < 0.1);
+        let (e, n) = to_utm(0.0, 3.0, 31, true);
+        assert!((e - 500_000.0).abs() < 1e-6 && n.abs() < 1e-6);
+
+        assert_eq!(zone(170.6667), 59);
+        assert_eq!(zone(-180.0), 1);
+        assert_eq!(zone(180.0), 1);
+        let (e, n) = to_utm(-45.8667, 170.6667, 59, false);
+        let (lat, lon) = from_utm(e, n, 59, false);
+        assert!((lat + 45.8667).abs() < 1e-8);
+        assert!((lon - 170.6667).abs() < 1e-8);
+    }
+}
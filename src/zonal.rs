@@ -0,0 +1,189 @@
+// Zonal statistics of subset pixels within polygons
+//
+// Each pixel is weighted by the fraction of its sinusoidal footprint inside the zone (see
+// ModisData::polygon_mask), so small fields are not dominated by pixels that only touch
+// their boundary.
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::mask::QcFilter;
+use crate::polygon::Polygon;
+use crate::structs::ModisData;
+
+/// Named set of polygons summarised together, e.g. one field
+#[derive(Debug, Clone, PartialEq)]
+pub struct Zone {
+    pub id: String,
+    pub polygons: Vec<Polygon>,
+}
+
+impl Zone {
+    /// One zone per feature of a FeatureCollection, or a single zone for any other
+    /// GeoJSON object
+    ///
+    /// Ids are taken from `id_property` when given, then the feature `id`, then the index.
+    pub fn from_geojson(
+        value: &Value,
+        id_property: Option<&str>,
+    ) -> Result<Vec<Zone>, Box<dyn std::error::Error>> {
+        let features = match value.get("type").and_then(Value::as_str) {
+            Some("FeatureCollection") => value
+                .get("features")
+                .and_then(Value::as_array)
+                .ok_or("missing features")?
+                .iter()
+                .collect(),
+            _ => vec![value],
+        };
+        features
+            .into_iter()
+            .enumerate()
+            .map(|(i, feature)| {
+                let id = id_property
+                    .and_then(|p| feature.get("properties")?.get(p))
+                    .or_else(|| feature.get("id"))
+                    .map(|id| match id {
+                        Value::String(s) => s.clone(),
+                        other => other.to_string(),
+                    })
+                    .unwrap_or_else(|| i.to_string());
+                Ok(Zone {
+                    id,
+                    polygons: Polygon::from_geojson(feature)?,
+                })
+            })
+            .collect()
+    }
+
+    pub fn from_wkt(id: &str, wkt: &str) -> Result<Zone, Box<dyn std::error::Error>> {
+        Ok(Zone {
+            id: id.to_string(),
+            polygons: Polygon::from_wkt(wkt)?,
+        })
+    }
+}
+
+/// Statistics of one zone on one date, values are scaled and weighted by pixel coverage
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ZonalStats {
+    pub zone: String,
+    pub modis_date: String,
+    pub calendar_date: String,
+    /// Pixels touching the zone with a valid value
+    pub valid_pixels: usize,
+    /// Valid share (0-1) of the zone's covered pixel area
+    pub coverage: f64,
+    pub mean: Option<f64>,
+    /// Weighted population standard deviation
+    pub std: Option<f64>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    /// Raw value with the largest covered area, meant for class bands like MCD12Q1 LC_Type1
+    pub majority: Option<i32>,
+}
+
+impl ModisData {
+    /// Statistics for every zone and date, masked pixels and those failing `qc` are left out
+    pub fn zonal_stats(&self, zones: &[Zone], qc: Option<&QcFilter>) -> Vec<ZonalStats> {
+        let mask = self.mask();
+        let mut stats = Vec::new();
+        for zone in zones {
+            let coverage = self.polygon_mask(&zone.polygons).coverage;
+            let total: f64 = coverage.iter().sum();
+            for subset in &self.subset {
+                // (weight, raw value, scaled value) of valid pixels in the zone
+                let pixels: Vec<(f64, i32, f64)> = coverage
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, w)| **w > 0.0)
+                    .filter(|(i, _)| qc.map(|q| q.passes(&subset.modis_date, *i)).unwrap_or(true))
+                    .filter_map(|(i, w)| {
+                        let raw = *subset.data.get(i)?;
+                        Some((*w, raw, self.masked_value(raw, &mask)?))
+                    })
+                    .collect();
+                let weight: f64 = pixels.iter().map(|p| p.0).sum();
+                let mean = (weight > 0.0)
+                    .then(|| pixels.iter().map(|(w, _, v)| w * v).sum::<f64>() / weight);
+                let std = mean.map(|m| {
+                    (pixels
+                        .iter()
+                        .map(|(w, _, v)| w * (v - m).powi(2))
+                        .sum::<f64>()
+                        / weight)
+                        .sqrt()
+                });
+                let mut areas: BTreeMap<i32, f64> = BTreeMap::new();
+                for (w, raw, _) in &pixels {
+                    *areas.entry(*raw).or_default() += w;
+                }
+                // Ties go to the smallest value
+                let majority = areas
+                    .into_iter()
+                    .max_by(|a, b| a.1.total_cmp(&b.1).then(b.0.cmp(&a.0)))
+                    .map(|(v, _)| v);
+
+                stats.push(ZonalStats {
+                    zone: zone.id.clone(),
+                    modis_date: subset.modis_date.clone(),
+                    calendar_date: subset.calendar_date.clone(),
+                    valid_pixels: pixels.len(),
+                    coverage: if total > 0.0 { weight / total } else { 0.0 },
+                    mean,
+                    std,
+                    min: pixels.iter().map(|p| p.2).reduce(f64::min),
+                    max: pixels.iter().map(|p| p.2).reduce(f64::max),
+                    majority,
+                });
+            }
+        }
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sinusoidal;
+    use crate::tests::otago;
+    use serde_json::json;
+
+    #[test]
+    fn test_zonal_stats() {
+        let mut data = otago();
+        data.subset[0].data[5] = 9;
+        // Pixels (1, 1) and (1, 2) fully, the right half of (1, 0) and the fill at (1, 3)
+        let (xmin, _, _, ymax) = data.pixel_bounds(1, 0);
+        let (_, ymin, xmax, _) = data.pixel_bounds(1, 3);
+        let xmin = xmin + data.cellsize / 2.0;
+        let ring: Vec<Value> = [(xmin, ymin), (xmax, ymin), (xmax, ymax), (xmin, ymax)]
+            .iter()
+            .chain([(xmin, ymin)].iter())
+            .map(|(x, y)| {
+                let (lat, lon) = sinusoidal::to_lat_lon(*x, *y);
+                json!([lon, lat])
+            })
+            .collect();
+        let geojson = json!({"type": "FeatureCollection", "features": [{
+            "type": "Feature",
+            "properties": {"name": "paddock"},
+            "geometry": {"type": "Polygon", "coordinates": [ring]}
+        }]});
+        let zones = Zone::from_geojson(&geojson, Some("name")).unwrap();
+        assert_eq!(zones[0].id, "paddock");
+
+        let stats = data.zonal_stats(&zones, None);
+        assert_eq!(stats.len(), 1);
+        let s = &stats[0];
+        // Values 0.9 (half weight), 0.9 and 0.4, 254 is fill
+        assert_eq!(s.valid_pixels, 3);
+        assert!((s.coverage - 2.5 / 3.5).abs() < 0.01);
+        assert!((s.mean.unwrap() - 1.75 / 2.5).abs() < 0.01);
+        assert_eq!((s.min, s.max), (Some(0.4), Some(0.9)));
+        assert_eq!(s.majority, Some(9));
+        assert!(s.std.unwrap() > 0.0);
+    }
+}